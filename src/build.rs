@@ -0,0 +1,507 @@
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, SystemTime};
+
+/// Tool driving the build, deciding how built files are located.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum BuildSystem {
+    /// 'dotnet build' of the .NET SDK.
+    Dotnet,
+    /// 'msbuild.exe', or 'xbuild' and 'msbuild' of Mono.
+    Msbuild,
+    /// Any command; assemblies written to '--build-output-dir' during the build are packed.
+    Directory,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Capture {
+    /// Ask MSBuild for the 'TargetPath' of the built project.
+    Properties,
+    /// Parse 'project -> path' lines printed by the build.
+    Output,
+}
+
+/// Locates the files produced by a build command.
+pub trait BuildAdapter {
+    /// Build command used when '--build' is not given.
+    fn default_command(&self) -> Option<&'static str>;
+
+    /// Returns the built file named by a line the build printed, if any.
+    fn parse_line(&self, line: &str) -> Option<PathBuf>;
+
+    /// Returns the built files located once the build command succeeded.
+    fn artifacts(&self, command: &[String], started: SystemTime) -> Result<Vec<PathBuf>>;
+
+    /// Command restoring the NuGet packages of a build command, if the build system has one.
+    fn restore_command(&self, command: &[String], locked: bool) -> Option<Vec<String>>;
+}
+
+/// Project and solution files named by the arguments of a build command.
+fn project_files(args: &[String]) -> impl Iterator<Item = String> {
+    args.iter()
+        .filter(|arg| {
+            let path = Path::new(arg);
+            !arg.starts_with('-')
+                && path.is_file()
+                && path
+                    .extension()
+                    .and_then(|ext| ext.to_str())
+                    .is_some_and(|ext| ext.ends_with("proj") || ext == "sln" || ext == "slnx")
+        })
+        .cloned()
+}
+
+/// Directories of the project and solution files named by a build command.
+/// Falls back to the working directory, where MSBuild looks for a project when none is named.
+pub fn project_dirs(command: &[String]) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    for file in project_files(command) {
+        let dir = match Path::new(&file).parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        if !dirs.contains(&dir) {
+            dirs.push(dir);
+        }
+    }
+    if dirs.is_empty() {
+        dirs.push(PathBuf::from("."));
+    }
+    dirs
+}
+
+/// Lines naming a built assembly as MSBuild prints them, e.g. 'MyMod -> bin/Debug/MyMod.dll'.
+/// Localized MSBuild versions separate the project and the path with different arrows.
+const BUILD_OUTPUT_PATTERNS: &[&str] = &[
+    r"^\s*\S.*? -> (?P<path>.+)$",
+    r"^\s*\S.*? → (?P<path>.+)$",
+    r"^\s*\S.*? => (?P<path>.+)$",
+    r"^\s*\S.*?\s*－＞\s*(?P<path>.+)$",
+];
+
+/// Compiles the patterns given with '--build-output-pattern', followed by the built-in ones.
+fn build_output_patterns(patterns: &[String]) -> Result<Vec<Regex>> {
+    patterns
+        .iter()
+        .map(String::as_str)
+        .chain(BUILD_OUTPUT_PATTERNS.iter().copied())
+        .map(|pattern| {
+            Regex::new(pattern)
+                .with_context(|| format!("Invalid build output pattern: {}", pattern))
+        })
+        .collect()
+}
+
+fn get_build_output(line: &str, patterns: &[Regex]) -> Option<PathBuf> {
+    patterns.iter().find_map(|pattern| {
+        let captures = pattern.captures(line)?;
+        let path = captures.name("path").or_else(|| captures.get(1))?;
+        // 'dotnet publish' also prints the publish directory.
+        Some(PathBuf::from(path.as_str().trim())).filter(|path| path.is_file())
+    })
+}
+
+/// Whether a build output line is an MSBuild or compiler warning,
+/// e.g. 'Main.cs(3,9): warning CS0168: The variable 'e' is declared but never used'.
+pub fn is_warning(line: &str) -> bool {
+    line.split_once(": warning ").is_some_and(|(_, rest)| {
+        rest.split_once(':').is_some_and(|(code, _)| {
+            !code.is_empty() && code.chars().all(|c| c.is_ascii_alphanumeric())
+        })
+    })
+}
+
+/// Translates the build options that affect where the assembly is written into
+/// MSBuild arguments, so the query resolves the same 'TargetPath'.
+/// '-o' sets the given property, 'OutputPath' for a build and 'PublishDir' for a publish.
+fn msbuild_query_args(args: &[String], output: &str) -> Vec<String> {
+    let mut query = Vec::new();
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        let property = match arg.as_str() {
+            "-c" | "--configuration" => "Configuration",
+            "-f" | "--framework" => "TargetFramework",
+            "-r" | "--runtime" => "RuntimeIdentifier",
+            "-o" | "--output" => output,
+            arg if arg.starts_with("-p:")
+                || arg.starts_with("--property:")
+                || arg.starts_with("/p:") =>
+            {
+                query.push(arg.to_string());
+                continue;
+            }
+            arg if !arg.starts_with('-') && Path::new(arg).exists() => {
+                // The project or solution to build.
+                query.push(arg.to_string());
+                continue;
+            }
+            _ => continue,
+        };
+        if let Some(value) = rest.next() {
+            query.push(format!("-p:{}={}", property, value));
+        }
+    }
+    query
+}
+
+/// Asks MSBuild for a property of the project, given the query arguments of the build.
+fn msbuild_property(
+    program: &str,
+    prefix: &[&str],
+    query: &[String],
+    env: &[(String, String)],
+    property: &str,
+) -> Result<String> {
+    let output = Command::new(program)
+        .args(prefix)
+        .arg("-nologo")
+        .arg(format!("-getProperty:{}", property))
+        .args(query)
+        .envs(env.iter().map(|(key, value)| (key, value)))
+        .output()
+        .context(
+            "Failed to query MSBuild, pass '--capture output' to parse the build output instead",
+        )?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if !output.status.success() {
+        bail!(
+            "Failed to query MSBuild, pass '--capture output' to parse the build output instead: {}",
+            stdout.trim()
+        );
+    }
+    Ok(stdout.trim().to_string())
+}
+
+/// Asks MSBuild for the path of the assembly the build produced.
+fn msbuild_target_path(
+    program: &str,
+    prefix: &[&str],
+    args: &[String],
+    env: &[(String, String)],
+) -> Result<PathBuf> {
+    let query = msbuild_query_args(args, "OutputPath");
+    let path = PathBuf::from(msbuild_property(
+        program,
+        prefix,
+        &query,
+        env,
+        "TargetPath",
+    )?);
+    if path.as_os_str().is_empty() || !path.is_file() {
+        bail!("MSBuild reported no built assembly: {}", path.display());
+    }
+    Ok(path)
+}
+
+/// Assemblies of the .NET runtime that 'dotnet publish' copies, but the game already provides.
+const FRAMEWORK_ASSEMBLIES: &[&str] = &[
+    "mscorlib.dll",
+    "netstandard.dll",
+    "System.dll",
+    "System.*.dll",
+    "Microsoft.CSharp.dll",
+    "Microsoft.VisualBasic*.dll",
+    "Microsoft.Win32.*.dll",
+    "WindowsBase.dll",
+];
+
+/// Assemblies of the game, Unity and the mod loader, which must never ship inside a mod.
+const GAME_ASSEMBLIES: &[&str] = &[
+    "Assembly-CSharp.dll",
+    "Assembly-CSharp-firstpass.dll",
+    "UnityEngine.dll",
+    "UnityEngine.*.dll",
+    "Unity.*.dll",
+    "NeoModLoader.dll",
+    "0Harmony.dll",
+];
+
+/// Assembly names are matched regardless of case, as on Windows.
+const NAME_OPTIONS: glob::MatchOptions = glob::MatchOptions {
+    case_sensitive: false,
+    require_literal_separator: false,
+    require_literal_leading_dot: false,
+};
+
+fn is_framework_assembly(name: &str) -> bool {
+    FRAMEWORK_ASSEMBLIES.iter().any(|pattern| {
+        glob::Pattern::new(pattern).is_ok_and(|pattern| pattern.matches_with(name, NAME_OPTIONS))
+    })
+}
+
+/// Compiles the assembly name patterns never packed from the build,
+/// the given ones followed by the game's own assemblies unless left out.
+pub fn assembly_denylist(patterns: &[String], game: bool) -> Result<Vec<glob::Pattern>> {
+    let game = if game { GAME_ASSEMBLIES } else { &[] };
+    patterns
+        .iter()
+        .map(String::as_str)
+        .chain(game.iter().copied())
+        .map(|pattern| {
+            glob::Pattern::new(pattern)
+                .with_context(|| format!("Invalid assembly pattern: {}", pattern))
+        })
+        .collect()
+}
+
+/// Whether the file name of an assembly matches the denylist.
+pub fn is_denied(path: &Path, denylist: &[glob::Pattern]) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| {
+            denylist
+                .iter()
+                .any(|pattern| pattern.matches_with(name, NAME_OPTIONS))
+        })
+}
+
+/// Collects the assemblies of a 'dotnet publish' directory, leaving out framework assemblies.
+/// The mod's own assembly comes last, as the one collected last is treated as the mod.
+fn published_assemblies(args: &[String], env: &[(String, String)]) -> Result<Vec<PathBuf>> {
+    let query = msbuild_query_args(args, "PublishDir");
+    let mut dir = PathBuf::from(msbuild_property(
+        "dotnet",
+        &["msbuild"],
+        &query,
+        env,
+        "PublishDir",
+    )?);
+    if dir.is_relative() {
+        let project = msbuild_property(
+            "dotnet",
+            &["msbuild"],
+            &query,
+            env,
+            "MSBuildProjectDirectory",
+        )?;
+        dir = Path::new(&project).join(dir);
+    }
+
+    let target = msbuild_target_path("dotnet", &["msbuild"], args, env)?;
+    let own = target.file_name().unwrap_or_default();
+
+    let entries = fs::read_dir(&dir)
+        .with_context(|| format!("Failed to read publish directory: {}", dir.display()))?;
+    let mut found = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        if path.is_file()
+            && path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("dll"))
+            && name != own
+            && !is_framework_assembly(name)
+        {
+            found.push(path);
+        }
+    }
+    found.sort();
+
+    let published = dir.join(own);
+    found.push(if published.is_file() {
+        published
+    } else {
+        target
+    });
+    Ok(found)
+}
+
+struct Dotnet {
+    publish: bool,
+    capture: Capture,
+    patterns: Vec<Regex>,
+    env: Vec<(String, String)>,
+}
+
+impl BuildAdapter for Dotnet {
+    fn default_command(&self) -> Option<&'static str> {
+        if self.publish {
+            Some("dotnet publish -p:DebugType=Portable")
+        } else {
+            Some("dotnet build -p:DebugType=Portable")
+        }
+    }
+
+    fn parse_line(&self, line: &str) -> Option<PathBuf> {
+        match self.capture {
+            Capture::Output => get_build_output(line, &self.patterns),
+            Capture::Properties => None,
+        }
+    }
+
+    fn artifacts(&self, command: &[String], _started: SystemTime) -> Result<Vec<PathBuf>> {
+        if self.capture != Capture::Properties {
+            return Ok(Vec::new());
+        }
+
+        // Options are only understood from a 'dotnet build' or 'dotnet publish' command.
+        let (verb, args) = match command {
+            [program, verb, args @ ..]
+                if (verb == "build" || verb == "publish")
+                    && Path::new(program)
+                        .file_stem()
+                        .is_some_and(|stem| stem == "dotnet") =>
+            {
+                (verb.as_str(), args)
+            }
+            _ => ("build", &[][..]),
+        };
+        if verb == "publish" {
+            return published_assemblies(args, &self.env);
+        }
+        Ok(vec![msbuild_target_path(
+            "dotnet",
+            &["msbuild"],
+            args,
+            &self.env,
+        )?])
+    }
+
+    fn restore_command(&self, command: &[String], locked: bool) -> Option<Vec<String>> {
+        let mut restore = vec!["dotnet".to_string(), "restore".to_string()];
+        if let [program, _, args @ ..] = command
+            && Path::new(program)
+                .file_stem()
+                .is_some_and(|stem| stem == "dotnet")
+        {
+            restore.extend(project_files(args));
+        }
+        if locked {
+            restore.push("--locked-mode".to_string());
+        }
+        Some(restore)
+    }
+}
+
+struct Msbuild {
+    capture: Capture,
+    patterns: Vec<Regex>,
+    env: Vec<(String, String)>,
+}
+
+impl BuildAdapter for Msbuild {
+    fn default_command(&self) -> Option<&'static str> {
+        Some("msbuild -restore -p:DebugType=Portable")
+    }
+
+    fn parse_line(&self, line: &str) -> Option<PathBuf> {
+        match self.capture {
+            Capture::Output => get_build_output(line, &self.patterns),
+            Capture::Properties => None,
+        }
+    }
+
+    fn artifacts(&self, command: &[String], _started: SystemTime) -> Result<Vec<PathBuf>> {
+        match (self.capture, command) {
+            (Capture::Properties, [program, args @ ..]) => {
+                Ok(vec![msbuild_target_path(program, &[], args, &self.env)?])
+            }
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    fn restore_command(&self, command: &[String], locked: bool) -> Option<Vec<String>> {
+        let (program, args) = command.split_first()?;
+        let mut restore = vec![program.clone(), "-t:Restore".to_string()];
+        restore.extend(project_files(args));
+        if locked {
+            restore.push("-p:RestoreLockedMode=true".to_string());
+        }
+        Some(restore)
+    }
+}
+
+struct Directory {
+    dir: PathBuf,
+}
+
+/// Collects the assemblies below a directory modified since the given time.
+fn modified_assemblies(dir: &Path, since: SystemTime, found: &mut Vec<PathBuf>) -> Result<()> {
+    let entries = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read directory: {}", dir.display()))?;
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_dir() {
+            modified_assemblies(&path, since, found)?;
+        } else if path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("dll"))
+            && fs::metadata(&path)
+                .and_then(|m| m.modified())
+                .is_ok_and(|modified| modified >= since)
+        {
+            found.push(path);
+        }
+    }
+    Ok(())
+}
+
+impl BuildAdapter for Directory {
+    fn default_command(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn parse_line(&self, _line: &str) -> Option<PathBuf> {
+        None
+    }
+
+    fn artifacts(&self, _command: &[String], started: SystemTime) -> Result<Vec<PathBuf>> {
+        if !self.dir.is_dir() {
+            bail!("Build output directory not found: {}", self.dir.display());
+        }
+
+        // File systems record modification times coarsely, FAT in steps of two seconds.
+        let since = started - Duration::from_secs(2);
+        let mut found = Vec::new();
+        modified_assemblies(&self.dir, since, &mut found)?;
+        found.sort();
+        Ok(found)
+    }
+
+    fn restore_command(&self, _command: &[String], _locked: bool) -> Option<Vec<String>> {
+        None
+    }
+}
+
+/// Creates the adapter of a build system for a build configuration.
+pub fn adapter(
+    system: BuildSystem,
+    capture: Capture,
+    patterns: &[String],
+    output_dir: &str,
+    config: Option<&str>,
+    env: &[(String, String)],
+    publish: bool,
+) -> Result<Box<dyn BuildAdapter>> {
+    if publish && !matches!(system, BuildSystem::Dotnet) {
+        bail!("'--publish' needs '--build-system dotnet'");
+    }
+
+    Ok(match system {
+        BuildSystem::Dotnet => Box::new(Dotnet {
+            publish,
+            capture,
+            patterns: build_output_patterns(patterns)?,
+            env: env.to_vec(),
+        }),
+        BuildSystem::Msbuild => Box::new(Msbuild {
+            capture,
+            patterns: build_output_patterns(patterns)?,
+            env: env.to_vec(),
+        }),
+        BuildSystem::Directory => Box::new(Directory {
+            dir: match config {
+                Some(config) => Path::new(output_dir).join(config),
+                None => PathBuf::from(output_dir),
+            },
+        }),
+    })
+}
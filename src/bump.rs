@@ -0,0 +1,170 @@
+use crate::console::info;
+use anyhow::{Context, Result, bail};
+use clap::{Args, ValueEnum};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Args)]
+pub struct BumpArgs {
+    /// Version component to increase.
+    #[arg(value_enum, help = "Version component to increase")]
+    part: Part,
+
+    /// Path of the 'mod.json' to update.
+    #[arg(
+        long,
+        default_value = "mod.json",
+        help = "Path of the 'mod.json' to update"
+    )]
+    mod_json: String,
+
+    /// Whether to also update '<Version>', '<AssemblyVersion>' and '<FileVersion>' in project files.
+    /// Every '.csproj' in the working directory is updated.
+    #[arg(
+        long,
+        help = "Whether to also update version elements in '.csproj' files"
+    )]
+    project: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Part {
+    /// Increase the major version, resetting minor and patch.
+    Major,
+    /// Increase the minor version, resetting patch.
+    Minor,
+    /// Increase the patch version.
+    Patch,
+}
+
+fn bumped(version: &str, part: Part) -> Result<String> {
+    let parts = version
+        .split('.')
+        .map(|p| p.parse::<u64>().ok())
+        .collect::<Option<Vec<_>>>()
+        .filter(|parts| parts.len() <= 3)
+        .with_context(|| format!("Version is not MAJOR.MINOR.PATCH: {}", version))?;
+
+    let [major, minor, patch] = [0, 1, 2].map(|i| parts.get(i).copied().unwrap_or_default());
+    let (major, minor, patch) = match part {
+        Part::Major => (major + 1, 0, 0),
+        Part::Minor => (major, minor + 1, 0),
+        Part::Patch => (major, minor, patch + 1),
+    };
+
+    Ok(format!("{}.{}.{}", major, minor, patch))
+}
+
+/// Finds the byte range of the string value of the first JSON key with the name, ignoring case.
+pub fn json_string_range(content: &str, key: &str) -> Option<(usize, usize)> {
+    let quoted = format!("\"{}\"", key);
+    let lower = content.to_ascii_lowercase();
+
+    lower
+        .match_indices(&quoted.to_ascii_lowercase())
+        .find_map(|(start, _)| {
+            let rest = &content[start + quoted.len()..];
+            let rest = rest.trim_start().strip_prefix(':')?.trim_start();
+            let value = rest.strip_prefix('"')?;
+            let begin = content.len() - value.len();
+            value.find('"').map(|end| (begin, begin + end))
+        })
+}
+
+/// Replaces the text of every '<tag>' element, keeping the number of version components.
+fn replace_elements(content: &str, tag: &str, version: &str) -> (String, usize) {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+
+    let mut replaced = String::new();
+    let mut count = 0;
+    let mut rest = content;
+    while let Some(start) = rest.find(&open) {
+        let value_start = start + open.len();
+        let Some(end) = rest[value_start..].find(&close) else {
+            break;
+        };
+
+        // Assembly versions have four components, e.g. '1.2.3.0'.
+        let old = rest[value_start..value_start + end].trim();
+        let mut new = version.to_string();
+        if old.split('.').count() == 4 {
+            new.push_str(".0");
+        }
+
+        replaced.push_str(&rest[..value_start]);
+        replaced.push_str(&new);
+        rest = &rest[value_start + end..];
+        count += 1;
+    }
+    replaced.push_str(rest);
+
+    (replaced, count)
+}
+
+fn bump_project(path: &Path, version: &str) -> Result<()> {
+    let mut content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read: {}", path.display()))?;
+
+    let mut count = 0;
+    for tag in ["Version", "AssemblyVersion", "FileVersion"] {
+        let (replaced, replacements) = replace_elements(&content, tag, version);
+        content = replaced;
+        count += replacements;
+    }
+
+    if count == 0 {
+        info!("No version elements in: {}", path.display());
+        return Ok(());
+    }
+
+    fs::write(path, content).with_context(|| format!("Failed to write: {}", path.display()))?;
+    info!("Updated {} version elements in: {}", count, path.display());
+    Ok(())
+}
+
+/// Increases the version in 'mod.json', and optionally in project files, keeping their formatting.
+pub fn run(args: &BumpArgs) -> Result<()> {
+    let path = Path::new(&args.mod_json);
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read: {}", path.display()))?;
+    serde_json::from_str::<serde_json::Value>(&content)
+        .with_context(|| format!("Failed to parse: {}", path.display()))?;
+
+    let Some((start, end)) = json_string_range(&content, "version") else {
+        bail!("No string 'version' in: {}", path.display());
+    };
+    let old = &content[start..end];
+    let new = bumped(old, args.part)?;
+
+    let updated = format!("{}{}{}", &content[..start], new, &content[end..]);
+    fs::write(path, updated).with_context(|| format!("Failed to write: {}", path.display()))?;
+    info!(
+        "Bumped version from {} to {} in: {}",
+        old,
+        new,
+        path.display()
+    );
+
+    if args.project {
+        let mut projects = fs::read_dir(".")
+            .context("Failed to read the working directory")?
+            .map(|entry| Ok(entry?.path()))
+            .collect::<Result<Vec<PathBuf>>>()?;
+        projects.retain(|p| {
+            p.extension()
+                .is_some_and(|e| e.eq_ignore_ascii_case("csproj"))
+        });
+        projects.sort();
+
+        if projects.is_empty() {
+            bail!("No '.csproj' in the working directory");
+        }
+        for project in &projects {
+            bump_project(project, &new)?;
+        }
+    }
+
+    info!("{}", new);
+    Ok(())
+}
@@ -0,0 +1,44 @@
+use crate::console::info;
+use crate::pack;
+use anyhow::{Context, Result};
+use clap::Args;
+use std::fs;
+
+#[derive(Args)]
+pub struct CleanArgs {
+    /// Number of the most recent packages of each mod to keep instead of deleting the directory.
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..),
+        help = "Number of the most recent packages of each mod to keep instead of deleting all"
+    )]
+    keep: Option<u32>,
+
+    /// Whether to also delete previews left in the temporary directory.
+    #[arg(
+        long,
+        help = "Whether to also delete previews left in the temporary directory"
+    )]
+    previews: bool,
+}
+
+/// Deletes the default output directory, or old packages in it, and on request previews.
+pub fn run(args: &CleanArgs) -> Result<()> {
+    let dir = pack::default_output_dir();
+    if let (Some(keep), true) = (args.keep, dir.exists()) {
+        let removed = pack::prune_packages(&dir, keep as usize, None)?;
+        info!("Removed {} old packages from: {}", removed, dir.display());
+    } else if dir.exists() {
+        fs::remove_dir_all(&dir).with_context(|| format!("Failed to remove: {}", dir.display()))?;
+        info!("Removed: {}", dir.display());
+    } else {
+        info!("Nothing to clean: {}", dir.display());
+    }
+
+    if args.previews {
+        pack::clean_previews()?;
+    }
+
+    Ok(())
+}
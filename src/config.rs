@@ -0,0 +1,154 @@
+use anyhow::{Context, Result, bail};
+use clap::parser::ValueSource;
+use clap::{ArgAction, ArgMatches, Command};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Names of the project config file, looked up in the working directory.
+const FILE_NAMES: &[&str] = &["packer.toml", "nmlpacker.toml"];
+
+/// Returns the project config file in the working directory, if there is one.
+pub fn find() -> Result<Option<PathBuf>> {
+    let found = FILE_NAMES
+        .iter()
+        .map(PathBuf::from)
+        .filter(|path| path.is_file())
+        .collect::<Vec<_>>();
+
+    if found.len() > 1 {
+        bail!("Both 'packer.toml' and 'nmlpacker.toml' exist, keep only one");
+    }
+    Ok(found.into_iter().next())
+}
+
+fn scalar(value: &toml::Value) -> Option<String> {
+    match value {
+        toml::Value::String(s) => Some(s.clone()),
+        toml::Value::Integer(i) => Some(i.to_string()),
+        toml::Value::Float(f) => Some(f.to_string()),
+        toml::Value::Boolean(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// Reads a config file into its table of options.
+pub fn read(path: &Path) -> Result<toml::Table> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read: {}", path.display()))?;
+    toml::from_str(&content).with_context(|| format!("Failed to parse: {}", path.display()))
+}
+
+/// Options of a named profile, from the '[profile.<name>]' table of a config file.
+pub fn profile<'a>(
+    path: &Path,
+    table: &'a toml::Table,
+    name: &str,
+) -> Result<Option<&'a toml::Table>> {
+    let Some(profiles) = table.get("profile") else {
+        return Ok(None);
+    };
+    let toml::Value::Table(profiles) = profiles else {
+        bail!(
+            "Invalid value for 'profile' in {}: expected a table of profiles, found {}",
+            path.display(),
+            profiles.type_str()
+        );
+    };
+    match profiles.get(name) {
+        Some(toml::Value::Table(profile)) => Ok(Some(profile)),
+        Some(value) => bail!(
+            "Invalid value for 'profile.{}' in {}: expected a table, found {}",
+            name,
+            path.display(),
+            value.type_str()
+        ),
+        None => Ok(None),
+    }
+}
+
+/// Converts the options read from a config file into arguments for every option not given on the command line.
+/// Keys are long option names, e.g. `artifact-glob = ["bin/**/*.dll"]`.
+pub fn args(
+    path: &Path,
+    table: &toml::Table,
+    command: &Command,
+    matches: &ArgMatches,
+) -> Result<Vec<OsString>> {
+    let mut args = Vec::new();
+    for (key, value) in table {
+        // Profiles are merged separately when selected.
+        if key == "profile" && matches!(value, toml::Value::Table(_)) {
+            continue;
+        }
+
+        let Some(arg) = command
+            .get_arguments()
+            .find(|arg| arg.get_long() == Some(key.as_str()))
+        else {
+            bail!("Unknown option '{}' in: {}", key, path.display());
+        };
+
+        if matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine) {
+            continue;
+        }
+
+        let flag = OsString::from(format!("--{}", key));
+        let invalid = |expected: &str| {
+            anyhow::anyhow!(
+                "Invalid value for '{}' in {}: expected {}, found {}",
+                key,
+                path.display(),
+                expected,
+                value.type_str()
+            )
+        };
+
+        // A table of includes maps sources to archive paths, e.g. `"libs/a.dll" = "Libs/a.dll"`.
+        if key == "include"
+            && let toml::Value::Table(map) = value
+        {
+            for (source, target) in map {
+                let target = scalar(target).ok_or_else(|| invalid("a table of paths"))?;
+                args.extend([flag.clone(), format!("{}:{}", source, target).into()]);
+            }
+            continue;
+        }
+
+        match arg.get_action() {
+            ArgAction::SetTrue => match value {
+                toml::Value::Boolean(true) => args.push(flag),
+                // A switch on by default cannot be turned off, which must not pass silently.
+                toml::Value::Boolean(false) if arg.get_default_values() == ["true"] => bail!(
+                    "Invalid value for '{}' in {}: it is enabled by default and cannot be disabled",
+                    key,
+                    path.display()
+                ),
+                toml::Value::Boolean(false) => {}
+                _ => return Err(invalid("a boolean")),
+            },
+            ArgAction::Count => match value {
+                toml::Value::Integer(count) if *count >= 0 => {
+                    args.extend((0..*count).map(|_| flag.clone()))
+                }
+                _ => return Err(invalid("a non-negative integer")),
+            },
+            ArgAction::Append => {
+                let values = match value {
+                    toml::Value::Array(values) => values.iter().collect::<Vec<_>>(),
+                    value => vec![value],
+                };
+                for value in values {
+                    let value = scalar(value).ok_or_else(|| invalid("an array of values"))?;
+                    args.extend([flag.clone(), value.into()]);
+                }
+            }
+            _ => {
+                let value = scalar(value).ok_or_else(|| invalid("a single value"))?;
+                args.extend([flag, value.into()]);
+            }
+        }
+    }
+
+    Ok(args)
+}
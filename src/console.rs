@@ -0,0 +1,186 @@
+use anyhow::{Context, Result};
+use indicatif::{ProgressBar, ProgressStyle};
+use std::fmt::Arguments;
+use std::fs::File;
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::time::Duration;
+
+/// Amount of detail printed to the console.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    /// Warnings and errors only.
+    Quiet,
+    /// Progress and results.
+    Normal,
+    /// Additional detail about each step.
+    Verbose,
+    /// Every file decision, parsed build line and written entry.
+    Trace,
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Normal as u8);
+
+/// Progress bar drawn while a long step runs, if any.
+static PROGRESS: Mutex<Option<ProgressBar>> = Mutex::new(None);
+
+/// Warnings printed since they were last taken.
+static WARNINGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// File mirroring everything written to the console, if enabled.
+static TEE: Mutex<Option<File>> = Mutex::new(None);
+
+/// Starts mirroring console output into the given file.
+/// The file begins with a header recording the invocation and the current time.
+pub fn tee(path: &Path) -> Result<()> {
+    let mut file =
+        File::create(path).with_context(|| format!("Failed to create file: {}", path.display()))?;
+
+    let args = std::env::args().collect::<Vec<_>>();
+    let invocation =
+        shlex::try_join(args.iter().map(String::as_str)).unwrap_or_else(|_| args.join(" "));
+
+    writeln!(file, "# {}", invocation)?;
+    writeln!(file, "# {}", crate::time::timestamp())?;
+    writeln!(file)?;
+
+    *TEE.lock().unwrap() = Some(file);
+    Ok(())
+}
+
+pub fn set_level(level: Level) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn level() -> Level {
+    match LEVEL.load(Ordering::Relaxed) {
+        0 => Level::Quiet,
+        1 => Level::Normal,
+        2 => Level::Verbose,
+        _ => Level::Trace,
+    }
+}
+
+pub fn is_verbose() -> bool {
+    level() >= Level::Verbose
+}
+
+/// Shows a progress bar over 'len' items, or a spinner counting items when the total is unknown.
+/// Nothing is drawn when quiet or outside an interactive terminal, leaving the plain output.
+pub fn start_progress(message: &'static str, len: Option<u64>) {
+    if level() < Level::Normal
+        || std::env::var_os("CI").is_some()
+        || !std::io::stdout().is_terminal()
+    {
+        return;
+    }
+
+    let bar = match len {
+        Some(len) => ProgressBar::new(len)
+            .with_style(ProgressStyle::with_template("{msg} [{bar:30}] {pos}/{len}").unwrap()),
+        None => ProgressBar::new_spinner()
+            .with_style(ProgressStyle::with_template("{spinner} {msg}: {pos}").unwrap()),
+    };
+    let bar = bar.with_message(message);
+    bar.enable_steady_tick(Duration::from_millis(100));
+
+    *PROGRESS.lock().unwrap() = Some(bar);
+}
+
+/// Advances the current progress bar by one item.
+pub fn advance() {
+    if let Some(bar) = PROGRESS.lock().unwrap().as_ref() {
+        bar.inc(1);
+    }
+}
+
+pub fn finish_progress() {
+    if let Some(bar) = PROGRESS.lock().unwrap().take() {
+        bar.finish_and_clear();
+    }
+}
+
+/// Runs a print with the progress bar hidden, so lines are not drawn over it.
+fn suspend(print: impl FnOnce()) {
+    match PROGRESS.lock().unwrap().as_ref() {
+        Some(bar) => bar.suspend(print),
+        None => print(),
+    }
+}
+
+/// Writes a line to the tee file only.
+pub fn record(args: Arguments) {
+    if let Some(file) = TEE.lock().unwrap().as_mut() {
+        let _ = writeln!(file, "{}", args);
+    }
+}
+
+/// Prints a line to stdout unless quiet. The tee file receives it regardless.
+pub fn print_info(args: Arguments) {
+    if level() >= Level::Normal {
+        suspend(|| println!("{}", args));
+    }
+    record(args);
+}
+
+pub fn print_warning(args: Arguments) {
+    suspend(|| eprintln!("Warning: {}", args));
+    record(format_args!("Warning: {}", args));
+    WARNINGS.lock().unwrap().push(args.to_string());
+}
+
+/// Returns the warnings printed so far and forgets them.
+pub fn take_warnings() -> Vec<String> {
+    std::mem::take(&mut *WARNINGS.lock().unwrap())
+}
+
+pub fn print_error(args: Arguments) {
+    suspend(|| eprintln!("{}", args));
+    record(args);
+}
+
+/// Prints a line to stdout, mirrored into the tee file.
+macro_rules! info {
+    () => {
+        $crate::console::print_info(format_args!(""))
+    };
+    ($($arg:tt)*) => {
+        $crate::console::print_info(format_args!($($arg)*))
+    };
+}
+
+/// Prints a line to stdout when verbose output is enabled.
+macro_rules! verbose {
+    ($($arg:tt)*) => {
+        if $crate::console::is_verbose() {
+            $crate::console::print_info(format_args!($($arg)*))
+        }
+    };
+}
+
+/// Prints a line to stdout when tracing every decision is enabled.
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        if $crate::console::level() >= $crate::console::Level::Trace {
+            $crate::console::print_info(format_args!($($arg)*))
+        }
+    };
+}
+
+/// Prints a warning to stderr, mirrored into the tee file.
+macro_rules! warning {
+    ($($arg:tt)*) => {
+        $crate::console::print_warning(format_args!($($arg)*))
+    };
+}
+
+/// Prints a line to stderr, mirrored into the tee file.
+macro_rules! error {
+    ($($arg:tt)*) => {
+        $crate::console::print_error(format_args!($($arg)*))
+    };
+}
+
+pub(crate) use {error, info, trace, verbose, warning};
@@ -0,0 +1,48 @@
+use crate::console::info;
+use crate::manifest;
+use anyhow::Result;
+use clap::Args;
+use std::path::Path;
+
+#[derive(Args)]
+pub struct DiffArgs {
+    /// Path of the older packed zip file.
+    #[arg(help = "Path of the older packed zip file")]
+    old: String,
+
+    /// Path of the newer packed zip file.
+    #[arg(help = "Path of the newer packed zip file")]
+    new: String,
+}
+
+/// Reports entries added, removed or changed between two packages, comparing content hashes.
+pub fn run(args: &DiffArgs) -> Result<()> {
+    let old = manifest::checksums(Path::new(&args.old))?;
+    let new = manifest::checksums(Path::new(&args.new))?;
+
+    let (mut added, mut removed, mut changed) = (0, 0, 0);
+    for (name, hash) in &old {
+        match new.get(name) {
+            None => {
+                info!("- {}", name);
+                removed += 1;
+            }
+            Some(new_hash) if new_hash != hash => {
+                info!("~ {}", name);
+                changed += 1;
+            }
+            Some(_) => {}
+        }
+    }
+    for name in new.keys().filter(|name| !old.contains_key(*name)) {
+        info!("+ {}", name);
+        added += 1;
+    }
+
+    if added + removed + changed == 0 {
+        info!("No differences in {} entries", new.len());
+    } else {
+        info!("{} added, {} removed, {} changed", added, removed, changed);
+    }
+    Ok(())
+}
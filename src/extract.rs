@@ -0,0 +1,93 @@
+use crate::console::{info, trace};
+use anyhow::{Context, Result, bail};
+use clap::Args;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Args)]
+pub struct ExtractArgs {
+    /// Path of the packed zip file.
+    #[arg(help = "Path of the packed zip file")]
+    archive: String,
+
+    /// Directory to extract into, named after the archive in the working directory if omitted.
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "Directory to extract into, named after the archive if omitted"
+    )]
+    to: Option<String>,
+}
+
+/// Turns an entry name into a relative path, accepting both separators.
+/// Returns nothing for names that would escape the target directory.
+fn entry_path(name: &str) -> Option<PathBuf> {
+    let name = name.replace('\\', "/");
+    if name.starts_with('/') || name.contains(':') {
+        return None;
+    }
+
+    let mut path = PathBuf::new();
+    for part in name.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => return None,
+            part => path.push(part),
+        }
+    }
+    Some(path)
+}
+
+/// Unpacks a mod package, refusing entries outside the target directory.
+pub fn run(args: &ExtractArgs) -> Result<()> {
+    let path = Path::new(&args.archive);
+    let target = match &args.to {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(path.file_stem().context("Invalid package name")?),
+    };
+
+    let file =
+        fs::File::open(path).with_context(|| format!("Failed to open: {}", path.display()))?;
+    let mut archive = zip::ZipArchive::new(file)
+        .with_context(|| format!("Failed to read archive: {}", path.display()))?;
+
+    // Check every name first so a malicious archive leaves nothing behind.
+    let mut paths = Vec::new();
+    for i in 0..archive.len() {
+        let entry = archive.by_index_raw(i)?;
+        let Some(relative) = entry_path(entry.name()) else {
+            bail!(
+                "Refusing entry outside the target directory: {}",
+                entry.name()
+            );
+        };
+        paths.push(relative);
+    }
+
+    let mut count = 0;
+    for (i, relative) in paths.iter().enumerate() {
+        let mut entry = archive.by_index(i)?;
+        let destination = target.join(relative);
+
+        if entry.is_dir() {
+            fs::create_dir_all(&destination).with_context(|| {
+                format!("Failed to create directory: {}", destination.display())
+            })?;
+            continue;
+        }
+
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+        }
+        let mut output = fs::File::create(&destination)
+            .with_context(|| format!("Failed to create file: {}", destination.display()))?;
+        std::io::copy(&mut entry, &mut output)
+            .with_context(|| format!("Failed to extract: {}", entry.name()))?;
+        trace!("Extracted: {}", destination.display());
+        count += 1;
+    }
+
+    info!("Extracted {} files into: {}", count, target.display());
+    Ok(())
+}
@@ -0,0 +1,50 @@
+use std::process::Command;
+
+/// Runs git in the working directory, returning its trimmed output on success.
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Abbreviated hash of the checked out commit.
+pub fn short_hash() -> Option<String> {
+    git(&["rev-parse", "--short", "HEAD"])
+}
+
+/// Tag pointing at the checked out commit, if any.
+pub fn exact_tag() -> Option<String> {
+    git(&["describe", "--tags", "--exact-match", "HEAD"])
+}
+
+/// Full hash of the checked out commit.
+pub fn hash() -> Option<String> {
+    git(&["rev-parse", "HEAD"])
+}
+
+/// Name of the checked out branch, missing for a detached head.
+pub fn branch() -> Option<String> {
+    git(&["symbolic-ref", "--short", "-q", "HEAD"])
+}
+
+/// Whether tracked files have uncommitted changes.
+pub fn is_dirty() -> Option<bool> {
+    git(&["status", "--porcelain", "--untracked-files=no"]).map(|status| !status.is_empty())
+}
+
+/// Fetch URL of the 'origin' remote.
+pub fn remote_url() -> Option<String> {
+    git(&["remote", "get-url", "origin"])
+}
+
+/// Author configured for commits, as 'Name <email>', or whichever of the two is set.
+pub fn author() -> Option<String> {
+    let name = git(&["config", "user.name"]).filter(|name| !name.is_empty());
+    let email = git(&["config", "user.email"]).filter(|email| !email.is_empty());
+    match (name, email) {
+        (Some(name), Some(email)) => Some(format!("{} <{}>", name, email)),
+        (name, email) => name.or(email),
+    }
+}
@@ -0,0 +1,182 @@
+use crate::console::info;
+use anyhow::{Context, Result, bail};
+use clap::Args;
+use serde_json::json;
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+#[derive(Args)]
+pub struct InitArgs {
+    /// Author written to 'mod.json', prompted for when missing.
+    #[arg(long, help = "Author written to 'mod.json', prompted for when missing")]
+    author: Option<String>,
+
+    /// Directory to create the mod in.
+    #[arg(default_value = ".", help = "Directory to create the mod in")]
+    directory: String,
+
+    /// Name written to 'mod.json', prompted for when missing.
+    #[arg(long, help = "Name written to 'mod.json', prompted for when missing")]
+    name: Option<String>,
+
+    /// Version written to 'mod.json', prompted for when missing.
+    #[arg(
+        long,
+        help = "Version written to 'mod.json', prompted for when missing"
+    )]
+    version: Option<String>,
+}
+
+/// Asks for a value on stdin, falling back to the default on an empty answer.
+fn prompt(label: &str, default: &str) -> Result<String> {
+    print!("{} [{}]: ", label, default);
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin()
+        .lock()
+        .read_line(&mut answer)
+        .context("Failed to read answer")?;
+
+    let answer = answer.trim();
+    Ok(if answer.is_empty() { default } else { answer }.to_string())
+}
+
+/// Turns a display name into a C# identifier, e.g. 'My Cool Mod' into 'MyCoolMod'.
+fn identifier(name: &str) -> String {
+    let identifier: String = name
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .flat_map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars))
+                .into_iter()
+                .flatten()
+        })
+        .collect();
+
+    match identifier.chars().next() {
+        None => "Mod".to_string(),
+        Some(first) if first.is_ascii_digit() => format!("_{}", identifier),
+        Some(_) => identifier,
+    }
+}
+
+fn write_new(path: &Path, content: &str) -> Result<()> {
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .with_context(|| format!("Failed to create file: {}", path.display()))?;
+    file.write_all(content.as_bytes())
+        .with_context(|| format!("Failed to write: {}", path.display()))?;
+    info!("Created: {}", path.display());
+    Ok(())
+}
+
+fn create_dir(path: &Path) -> Result<()> {
+    fs::create_dir_all(path)
+        .with_context(|| format!("Failed to create directory: {}", path.display()))?;
+    info!("Created: {}", path.display());
+    Ok(())
+}
+
+fn entry_class(namespace: &str) -> String {
+    format!(
+        r#"using NeoModLoader.api;
+
+namespace {namespace}
+{{
+    public class ModClass : BasicMod<ModClass>
+    {{
+        protected override void OnModLoad()
+        {{
+        }}
+    }}
+}}
+"#
+    )
+}
+
+fn project(namespace: &str) -> String {
+    format!(
+        r#"<Project Sdk="Microsoft.NET.Sdk">
+
+  <PropertyGroup>
+    <TargetFramework>net48</TargetFramework>
+    <LangVersion>latest</LangVersion>
+    <RootNamespace>{namespace}</RootNamespace>
+    <AssemblyName>{namespace}</AssemblyName>
+    <WorldBoxPath Condition="'$(WorldBoxPath)' == ''">C:\Program Files (x86)\Steam\steamapps\common\worldbox</WorldBoxPath>
+    <ManagedPath>$(WorldBoxPath)\worldbox_Data\Managed</ManagedPath>
+  </PropertyGroup>
+
+  <ItemGroup>
+    <Reference Include="Assembly-CSharp" HintPath="$(ManagedPath)\Assembly-CSharp.dll" Private="false" />
+    <Reference Include="UnityEngine" HintPath="$(ManagedPath)\UnityEngine.dll" Private="false" />
+    <Reference Include="UnityEngine.CoreModule" HintPath="$(ManagedPath)\UnityEngine.CoreModule.dll" Private="false" />
+    <Reference Include="NeoModLoader" HintPath="$(WorldBoxPath)\worldbox_Data\StreamingAssets\mods\NeoModLoader.dll" Private="false" />
+  </ItemGroup>
+
+</Project>
+"#
+    )
+}
+
+/// Scaffolds a new NeoModLoader mod in the given directory.
+pub fn run(args: &InitArgs) -> Result<()> {
+    let dir = Path::new(&args.directory);
+    if dir.join("mod.json").exists() {
+        bail!("A mod already exists in: {}", dir.display());
+    }
+
+    let default_name = fs::canonicalize(dir)
+        .unwrap_or_else(|_| dir.to_path_buf())
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "MyMod".to_string());
+
+    let name = match &args.name {
+        Some(name) => name.clone(),
+        None => prompt("Name", &default_name)?,
+    };
+    let version = match &args.version {
+        Some(version) => version.clone(),
+        None => prompt("Version", "1.0.0")?,
+    };
+    let author = match &args.author {
+        Some(author) => author.clone(),
+        None => prompt("Author", "Anonymous")?,
+    };
+
+    let namespace = identifier(&name);
+    let mod_json = json!({
+        "name": name,
+        "author": author,
+        "version": version,
+        "description": "",
+        "GUID": format!("{}.{}", identifier(&author), namespace),
+    });
+
+    create_dir(dir)?;
+    write_new(
+        &dir.join("mod.json"),
+        &format!("{}\n", serde_json::to_string_pretty(&mod_json)?),
+    )?;
+    create_dir(&dir.join("assets"))?;
+    create_dir(&dir.join("Locals"))?;
+    create_dir(&dir.join("Code"))?;
+    write_new(
+        &dir.join("Code").join("ModClass.cs"),
+        &entry_class(&namespace),
+    )?;
+    write_new(
+        &dir.join(format!("{}.csproj", namespace)),
+        &project(&namespace),
+    )?;
+
+    info!("Initialized mod '{}' in: {}", name, dir.display());
+    Ok(())
+}
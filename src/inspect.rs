@@ -0,0 +1,106 @@
+use crate::console::info;
+use crate::pack::format_size;
+use anyhow::{Context, Result};
+use clap::Args;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+#[derive(Args)]
+pub struct InspectArgs {
+    /// Path of the packed zip file.
+    #[arg(help = "Path of the packed zip file")]
+    archive: String,
+
+    /// Whether to print the listing as JSON instead of a table.
+    #[arg(long, help = "Whether to print the listing as JSON instead of a table")]
+    json: bool,
+}
+
+#[derive(Serialize)]
+struct Entry {
+    name: String,
+    /// Uncompressed size in bytes.
+    size: u64,
+    compressed_size: u64,
+    method: String,
+}
+
+/// Entries of an archive with totals over its files, printed by '--json'.
+#[derive(Serialize)]
+struct Listing {
+    entries: Vec<Entry>,
+    files: usize,
+    size: u64,
+    compressed_size: u64,
+}
+
+fn read_listing(path: &Path) -> Result<Listing> {
+    let file =
+        fs::File::open(path).with_context(|| format!("Failed to open: {}", path.display()))?;
+    let mut archive = zip::ZipArchive::new(file)
+        .with_context(|| format!("Failed to read archive: {}", path.display()))?;
+
+    let mut listing = Listing {
+        entries: Vec::new(),
+        files: 0,
+        size: 0,
+        compressed_size: 0,
+    };
+    for i in 0..archive.len() {
+        let entry = archive.by_index_raw(i)?;
+        if !entry.is_dir() {
+            listing.files += 1;
+            listing.size += entry.size();
+            listing.compressed_size += entry.compressed_size();
+        }
+        listing.entries.push(Entry {
+            name: entry.name().to_string(),
+            size: entry.size(),
+            compressed_size: entry.compressed_size(),
+            method: entry.compression().to_string(),
+        });
+    }
+
+    Ok(listing)
+}
+
+/// Lists the entries of a packed archive.
+pub fn run(args: &InspectArgs) -> Result<()> {
+    let path = Path::new(&args.archive);
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&read_listing(path)?)?);
+        return Ok(());
+    }
+
+    print_listing(path)
+}
+
+pub fn print_listing(path: &Path) -> Result<()> {
+    let listing = read_listing(path)?;
+
+    info!();
+    info!(
+        "{:>14}{:>14}  {:<10}{}",
+        "Size", "Compressed", "Method", "Entry"
+    );
+    for entry in &listing.entries {
+        info!(
+            "{:>14}{:>14}  {:<10}{}",
+            format_size(entry.size),
+            format_size(entry.compressed_size),
+            entry.method,
+            entry.name
+        );
+    }
+    info!(
+        "{:>14}{:>14}  {:<10}{} files",
+        format_size(listing.size),
+        format_size(listing.compressed_size),
+        "",
+        listing.files
+    );
+    info!();
+
+    Ok(())
+}
@@ -0,0 +1,321 @@
+use crate::console::{info, verbose, warning};
+use crate::pack;
+use anyhow::{Context, Result, bail};
+use clap::Args;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Args)]
+pub struct InstallArgs {
+    /// Path of the packed zip file, the newest one in the default output directory if omitted.
+    #[arg(help = "Path of the packed zip file, the newest in 'bin/Mod' if omitted")]
+    archive: Option<String>,
+
+    /// Whether to extract the package into a folder instead of copying the zip file.
+    #[arg(
+        long,
+        help = "Whether to extract the package into a folder instead of copying the zip file"
+    )]
+    extract: bool,
+
+    /// WorldBox installation directory, detected from the Steam libraries if omitted.
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "WorldBox installation directory, detected from the Steam libraries if omitted"
+    )]
+    game_dir: Option<String>,
+
+    /// Whether to also remove other copies of the mod in the mods directory, such as unpacked source folders.
+    /// Without it only earlier installs named like packages of the mod are removed, and other copies are listed.
+    #[arg(
+        long,
+        help = "Whether to also remove other copies of the mod in the mods directory"
+    )]
+    replace: bool,
+}
+
+/// Default Steam installation directories of the current platform.
+fn steam_roots() -> Vec<PathBuf> {
+    let mut roots = Vec::new();
+
+    if cfg!(windows) {
+        for var in ["ProgramFiles(x86)", "ProgramFiles"] {
+            if let Some(dir) = std::env::var_os(var) {
+                roots.push(PathBuf::from(dir).join("Steam"));
+            }
+        }
+    } else if let Some(home) = std::env::var_os("HOME").map(PathBuf::from) {
+        if cfg!(target_os = "macos") {
+            roots.push(home.join("Library/Application Support/Steam"));
+        } else {
+            roots.push(home.join(".steam/steam"));
+            roots.push(home.join(".local/share/Steam"));
+            roots.push(home.join(".var/app/com.valvesoftware.Steam/.local/share/Steam"));
+        }
+    }
+
+    roots
+}
+
+/// Reads the library paths listed in 'steamapps/libraryfolders.vdf'.
+fn library_folders(root: &Path) -> Vec<PathBuf> {
+    let Ok(content) = fs::read_to_string(root.join("steamapps").join("libraryfolders.vdf")) else {
+        return Vec::new();
+    };
+
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split('"').filter(|part| !part.trim().is_empty());
+            match (parts.next(), parts.next()) {
+                (Some("path"), Some(path)) => Some(PathBuf::from(path.replace("\\\\", "\\"))),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Finds the WorldBox installation in any known Steam library.
+fn find_game_dir() -> Result<PathBuf> {
+    let mut libraries = Vec::new();
+    for root in steam_roots() {
+        if root.is_dir() {
+            libraries.push(root.clone());
+            libraries.extend(library_folders(&root));
+        }
+    }
+
+    for library in &libraries {
+        let dir = library.join("steamapps").join("common").join("worldbox");
+        verbose!("Looking for WorldBox in: {}", dir.display());
+        if dir.is_dir() {
+            return Ok(dir);
+        }
+    }
+
+    bail!("WorldBox installation not found in any Steam library, pass '--game-dir'");
+}
+
+/// Returns the most recently modified zip file in the default output directory.
+pub fn newest_package() -> Result<PathBuf> {
+    let dir = pack::default_output_dir();
+    let entries = fs::read_dir(&dir)
+        .with_context(|| format!("Failed to read directory: {}", dir.display()))?;
+
+    let mut newest = None;
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "zip") {
+            let modified = fs::metadata(&path)?.modified()?;
+            if newest.as_ref().is_none_or(|(time, _)| modified > *time) {
+                newest = Some((modified, path));
+            }
+        }
+    }
+
+    newest
+        .map(|(_, path)| path)
+        .with_context(|| format!("No package found in: {}", dir.display()))
+}
+
+/// Identity of an installed mod, read from its 'mod.json'.
+struct ModIdentity {
+    guid: Option<String>,
+    name: Option<String>,
+}
+
+impl ModIdentity {
+    fn parse(content: &str) -> Option<Self> {
+        let value: serde_json::Value = serde_json::from_str(content).ok()?;
+        let field = |key: &str| value.get(key)?.as_str().map(str::to_string);
+        Some(Self {
+            guid: field("GUID"),
+            name: field("name"),
+        })
+    }
+
+    /// Reads the 'mod.json' at the root of a package or mod folder.
+    fn read(path: &Path) -> Option<Self> {
+        let content = if path.is_dir() {
+            fs::read_to_string(path.join("mod.json")).ok()?
+        } else {
+            let mut archive = zip::ZipArchive::new(fs::File::open(path).ok()?).ok()?;
+            std::io::read_to_string(archive.by_name("mod.json").ok()?).ok()?
+        };
+        Self::parse(&content)
+    }
+
+    /// Compares by GUID when both have one, by name otherwise.
+    fn same_mod(&self, other: &Self) -> bool {
+        match (&self.guid, &other.guid) {
+            (Some(guid), Some(other)) => guid == other,
+            _ => self.name.is_some() && self.name == other.name,
+        }
+    }
+}
+
+/// Finds other copies of a mod in the mods directory, as zip files or folders of any version.
+/// Returns the earlier installs, named like packages of the mod, apart from other copies such as source checkouts.
+fn previous_installs(mods_dir: &Path, archive: &Path) -> Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+    let name = archive.file_name().context("Invalid package name")?;
+    let stem = archive.file_stem().context("Invalid package name")?;
+    let identity = ModIdentity::read(archive);
+    let group = pack::package_group_of(archive);
+
+    let entries = fs::read_dir(mods_dir)
+        .with_context(|| format!("Failed to read directory: {}", mods_dir.display()))?;
+    let mut installs = Vec::new();
+    let mut others = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        let file_name = path.file_name().unwrap_or_default();
+        if file_name == name || file_name == stem {
+            installs.push(path);
+            continue;
+        }
+
+        let package = path.is_dir() || path.extension().is_some_and(|ext| ext == "zip");
+        let same = package
+            && identity.as_ref().is_some_and(|identity| {
+                ModIdentity::read(&path).is_some_and(|other| identity.same_mod(&other))
+            });
+        if !same {
+            continue;
+        }
+        if group.is_some() && pack::package_group_of(&path) == group {
+            installs.push(path);
+        } else {
+            others.push(path);
+        }
+    }
+    Ok((installs, others))
+}
+
+fn remove_existing(path: &Path) -> Result<()> {
+    if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else if path.exists() {
+        fs::remove_file(path)
+    } else {
+        return Ok(());
+    }
+    .with_context(|| format!("Failed to remove: {}", path.display()))
+}
+
+pub fn run(args: &InstallArgs) -> Result<()> {
+    install(
+        args.archive.as_deref().map(Path::new),
+        args.game_dir.as_deref().map(Path::new),
+        args.extract,
+        args.replace,
+    )
+}
+
+/// Copies or extracts a packed mod into the NeoModLoader mods directory of WorldBox.
+pub fn install(
+    archive: Option<&Path>,
+    game_dir: Option<&Path>,
+    extract: bool,
+    replace: bool,
+) -> Result<()> {
+    let archive = match archive {
+        Some(archive) => archive.to_path_buf(),
+        None => newest_package()?,
+    };
+    if !archive.is_file() {
+        bail!("Package not found: {}", archive.display());
+    }
+
+    let game_dir = match game_dir {
+        Some(dir) => dir.to_path_buf(),
+        None => find_game_dir()?,
+    };
+    let mods_dir = game_dir.join("Mods");
+    fs::create_dir_all(&mods_dir)
+        .with_context(|| format!("Failed to create directory: {}", mods_dir.display()))?;
+
+    // Remove previous installs in either form and of any version, so the mod is not loaded twice.
+    let (installs, others) = previous_installs(&mods_dir, &archive)?;
+    for path in installs {
+        info!("Removing previous install: {}", path.display());
+        remove_existing(&path)?;
+    }
+    for path in others {
+        if replace {
+            info!("Removing other copy of the mod: {}", path.display());
+            remove_existing(&path)?;
+        } else {
+            warning!(
+                "Another copy of the mod is loaded from: {}, pass '--replace' to remove it",
+                path.display()
+            );
+        }
+    }
+
+    let name = archive.file_name().context("Invalid package name")?;
+    let stem = archive.file_stem().context("Invalid package name")?;
+
+    let target = if extract {
+        let target = mods_dir.join(stem);
+
+        let file = fs::File::open(&archive)
+            .with_context(|| format!("Failed to open: {}", archive.display()))?;
+        zip::ZipArchive::new(file)
+            .with_context(|| format!("Failed to read archive: {}", archive.display()))?
+            .extract(&target)
+            .with_context(|| format!("Failed to extract into: {}", target.display()))?;
+        target
+    } else {
+        let target = mods_dir.join(name);
+        fs::copy(&archive, &target)
+            .with_context(|| format!("Failed to copy into: {}", target.display()))?;
+        target
+    };
+
+    info!("Installed mod at: {}", target.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pack::TempDir;
+
+    const MOD_JSON: &str =
+        r#"{"GUID": "com.example.mymod", "name": "MyMod", "version": "VERSION"}"#;
+
+    fn write_package(path: &Path, version: &str) {
+        let mut zip = zip::ZipWriter::new(fs::File::create(path).unwrap());
+        zip.start_file("mod.json", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        std::io::Write::write_all(&mut zip, MOD_JSON.replace("VERSION", version).as_bytes())
+            .unwrap();
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn source_folders_of_the_mod_are_kept() {
+        let dir = TempDir::new("test-install").unwrap();
+        let mods = dir.0.join("Mods");
+        for folder in ["MyMod", "MyMod-0.8.0"] {
+            fs::create_dir_all(mods.join(folder)).unwrap();
+            fs::write(
+                mods.join(folder).join("mod.json"),
+                MOD_JSON.replace("VERSION", "0.8.0"),
+            )
+            .unwrap();
+        }
+        write_package(&mods.join("MyMod-0.9.0.zip"), "0.9.0");
+        let archive = dir.0.join("MyMod-1.0.0.zip");
+        write_package(&archive, "1.0.0");
+
+        let (mut installs, others) = previous_installs(&mods, &archive).unwrap();
+        installs.sort();
+        assert_eq!(
+            installs,
+            [mods.join("MyMod-0.8.0"), mods.join("MyMod-0.9.0.zip")]
+        );
+        assert_eq!(others, [mods.join("MyMod")]);
+    }
+}
@@ -0,0 +1,132 @@
+use std::path::Path;
+
+const FILE_NAMES: &[&str] = &["LICENSE", "LICENCE", "COPYING", "UNLICENSE"];
+
+const FILE_EXTENSIONS: &[&str] = &["md", "txt"];
+
+/// Returns whether the target is a license file placed at the package root.
+pub fn is_license_file(target: &Path) -> bool {
+    if target.parent().is_some_and(|p| !p.as_os_str().is_empty()) {
+        return false;
+    }
+
+    let stem = target.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+    let extension = target.extension().and_then(|e| e.to_str());
+
+    FILE_NAMES
+        .iter()
+        .any(|name| stem.eq_ignore_ascii_case(name))
+        && extension.is_none_or(|e| FILE_EXTENSIONS.iter().any(|x| e.eq_ignore_ascii_case(x)))
+}
+
+/// Characteristic clauses of known licenses, each paired with clauses that rule it out.
+/// Clauses are normalized like the license text; more specific licenses are listed first.
+const FINGERPRINTS: &[(&str, &[&str], &[&str])] = &[
+    (
+        "AGPL-3.0",
+        &["gnu affero general public license", "version 3"],
+        &[],
+    ),
+    (
+        "LGPL-3.0",
+        &["gnu lesser general public license", "version 3"],
+        &[],
+    ),
+    (
+        "LGPL-2.1",
+        &["gnu lesser general public license", "version 2 1"],
+        &[],
+    ),
+    (
+        "GPL-3.0",
+        &["gnu general public license", "version 3 29 june 2007"],
+        &[],
+    ),
+    (
+        "GPL-2.0",
+        &["gnu general public license", "version 2 june 1991"],
+        &[],
+    ),
+    (
+        "Apache-2.0",
+        &[
+            "apache license",
+            "version 2 0",
+            "terms and conditions for use reproduction and distribution",
+        ],
+        &[],
+    ),
+    (
+        "Apache-2.0",
+        &["licensed under the apache license version 2 0"],
+        &[],
+    ),
+    ("MPL-2.0", &["mozilla public license version 2 0"], &[]),
+    ("BSL-1.0", &["boost software license version 1 0"], &[]),
+    (
+        "Unlicense",
+        &["this is free and unencumbered software released into the public domain"],
+        &[],
+    ),
+    ("CC0-1.0", &["cc0 1 0 universal"], &[]),
+    (
+        "MIT",
+        &[
+            "permission is hereby granted free of charge to any person obtaining a copy",
+            "the above copyright notice and this permission notice shall be included",
+        ],
+        &[],
+    ),
+    (
+        "ISC",
+        &[
+            "permission to use copy modify and or distribute this software for any purpose",
+            "provided that the above copyright notice and this permission notice appear in all copies",
+        ],
+        &[],
+    ),
+    (
+        "0BSD",
+        &["permission to use copy modify and or distribute this software for any purpose"],
+        &[],
+    ),
+    (
+        "BSD-3-Clause",
+        &[
+            "redistribution and use in source and binary forms with or without modification are permitted",
+            "neither the name of",
+        ],
+        &[],
+    ),
+    (
+        "BSD-2-Clause",
+        &[
+            "redistribution and use in source and binary forms with or without modification are permitted",
+        ],
+        &["neither the name of"],
+    ),
+];
+
+/// Lowercases the text and reduces it to words separated by single spaces.
+fn normalize(content: &str) -> String {
+    content
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Detects the SPDX identifier of a license from the text of a license file.
+/// A license matches when all of its characteristic clauses appear and none that rule it out.
+pub fn detect(content: &str) -> Option<&'static str> {
+    let text = normalize(content);
+
+    FINGERPRINTS
+        .iter()
+        .find(|(_, required, excluded)| {
+            required.iter().all(|clause| text.contains(clause))
+                && !excluded.iter().any(|clause| text.contains(clause))
+        })
+        .map(|(license, _, _)| *license)
+}
@@ -1,284 +1,276 @@
-use anyhow::{Context, Result, anyhow, bail};
-use clap::Parser;
-use serde::Deserialize;
+use anyhow::{Context, Result, bail};
+use clap::{ArgMatches, Command, CommandFactory, Parser, Subcommand};
+use std::ffi::OsString;
 use std::fs;
-use std::io::{BufRead, BufReader};
-use std::path::{Path, PathBuf, absolute};
-use std::process::Stdio;
-use zip::write::SimpleFileOptions;
+use std::path::{Path, PathBuf};
+
+mod build;
+mod bump;
+mod clean;
+mod config;
+mod console;
+mod diff;
+mod extract;
+mod git;
+mod init;
+mod inspect;
+mod install;
+mod license;
+mod manifest;
+mod nmlignore;
+mod pack;
+mod pe;
+mod publish;
+mod signing;
+mod time;
+mod watch;
+mod workspace;
 
 #[derive(Parser)]
-#[command(version)]
+#[command(version, args_conflicts_with_subcommands = true)]
 struct Cli {
-    /// Asset directories to be included in the package.
-    #[arg(long, default_values = &["assets"], help = "Asset directories to be included in the package"
-    )]
-    assets: Vec<String>,
+    #[command(subcommand)]
+    command: Option<Commands>,
 
-    /// The command used to build the project
+    /// Project root to run in, instead of the working directory.
+    /// Config, 'mod.json', sources and relative paths given on the command line resolve from it.
     #[arg(
+        short = 'C',
         long,
-        default_value = "dotnet build -p:DebugType=Portable",
-        help = "The command used to build the project"
+        global = true,
+        value_name = "DIR",
+        help = "Project root to run in, instead of the working directory"
     )]
-    build: String,
-
-    /// Whether to build binary.
-    #[arg(short, long, help = "Whether to build binary")]
-    compile: bool,
-
-    /// Additional files or directories to include.
-    /// Default values are provided for forward compatibility with existing mod structures.
-    #[arg(long, default_values = &["Locals", "LICENSE", "default_config.json", "icon.png", "mod.json"], help = "Additional files or directories to include")]
-    include: Vec<String>,
-
-    /// The final output path of the packed zip file.
-    /// If not specified, it defaults to the 'bin/Mod/<name>-<version>.zip'.
-    #[arg(short, long, help = "The final output path of the packed zip file")]
-    output: Option<String>,
-
-    /// Whether to include PDB files.
-    #[arg(long, default_value_t = true, help = "Whether to include PDB files")]
-    pdb: bool,
+    project_dir: Option<PathBuf>,
 
-    /// Source code directories.
-    /// Default values are provided for compatibility with various project layouts.
-    #[arg(long, default_values = &["Code", "code", "src"], help = "Source code directories")]
-    sources: Vec<String>,
+    /// Options of the default 'pack' command.
+    #[command(flatten)]
+    pack: pack::PackArgs,
 }
 
-#[derive(Deserialize)]
-struct Mod {
-    name: String,
-    version: String,
+#[derive(Subcommand)]
+enum Commands {
+    /// Pack the mod into a zip file (default).
+    Pack(pack::PackArgs),
+    /// Run every packing check without writing the package.
+    Validate(pack::PackArgs),
+    /// List the entries of a packed zip file.
+    Inspect(inspect::InspectArgs),
+    /// Delete packed output and previews.
+    Clean(clean::CleanArgs),
+    /// Scaffold a new mod with 'mod.json', folders, an entry class and a project.
+    #[command(visible_alias = "new")]
+    Init(init::InitArgs),
+    /// Copy a packed zip file into the mods directory of WorldBox.
+    Install(install::InstallArgs),
+    /// Repack whenever assets, included files or sources change.
+    Watch(watch::WatchArgs),
+    /// Increase the version in 'mod.json' and optionally project files.
+    Bump(bump::BumpArgs),
+    /// Check the detached signature of a packed zip file.
+    Verify(signing::VerifyArgs),
+    /// Upload a packed zip file to a distribution platform.
+    Publish(publish::PublishArgs),
+    /// Unpack a packed zip file into a directory.
+    Extract(extract::ExtractArgs),
+    /// Compare the entries of two packed zip files.
+    Diff(diff::DiffArgs),
 }
 
-struct File {
-    pub source: PathBuf,
-    pub target: PathBuf,
-}
-
-fn main() -> Result<()> {
-    let cli = Cli::parse();
-
-    let mut files = Vec::new();
-
-    collect_assets_and_include(&cli.assets, &cli.include, &mut files)?;
+/// Splices the contents of '@file' arguments into the argument list.
+/// Files are split with shell quoting rules and may reference other files one level deep.
+fn expand_response_files(
+    args: impl IntoIterator<Item = OsString>,
+    depth: usize,
+) -> Result<Vec<OsString>> {
+    let mut expanded = Vec::new();
+    for arg in args {
+        let Some(path) = arg.to_str().and_then(|a| a.strip_prefix('@')) else {
+            expanded.push(arg);
+            continue;
+        };
 
-    let output = generate_output_path(&cli.output, &files)?;
+        if depth > 1 {
+            bail!("Response files nested too deeply: {}", path);
+        }
 
-    if cli.compile {
-        compile(&cli.build, cli.pdb, &mut files)?;
-    } else {
-        collect_sources(&cli.sources, &mut files)?;
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read response file: {}", path))?;
+        let parts = shlex::split(&content)
+            .with_context(|| format!("Invalid quoting in response file: {}", path))?;
+        expanded.extend(expand_response_files(
+            parts.into_iter().map(OsString::from),
+            depth + 1,
+        )?);
     }
-
-    zip(&output, &files)?;
-
-    print_packed_message(&output)?;
-
-    Ok(())
+    Ok(expanded)
 }
 
-fn find_file(files: &[File], name: &str) -> Option<PathBuf> {
-    files
+/// Takes '--project-dir' out of the arguments before parsing, as the config file is looked up in it.
+/// Removing it also keeps it from counting as a pack option next to a subcommand.
+fn take_project_dir(args: &mut Vec<OsString>) -> Option<PathBuf> {
+    let end = args
         .iter()
-        .filter(|file| file.source.exists())
-        .find(|file| file.source.file_name() == Some(std::ffi::OsStr::new(name)))
-        .map(|file| file.source.clone())
+        .position(|arg| arg == "--")
+        .unwrap_or(args.len());
+    for index in 0..end {
+        let arg = args[index].to_string_lossy();
+        let (dir, len) = if arg == "-C" || arg == "--project-dir" {
+            (args[..end].get(index + 1).map(PathBuf::from), 2)
+        } else if let Some(dir) = arg.strip_prefix("--project-dir=") {
+            (Some(PathBuf::from(dir)), 1)
+        } else if let Some(dir) = arg.strip_prefix("-C").filter(|dir| !dir.is_empty()) {
+            (Some(PathBuf::from(dir)), 1)
+        } else {
+            continue;
+        };
+        // Without a value it is left for the parser to report.
+        if dir.is_some() {
+            args.drain(index..index + len);
+        }
+        return dir;
+    }
+    None
 }
 
-const ARROW: &str = " -> ";
-
-fn get_dotnet_build(line: &str) -> Option<PathBuf> {
-    line.contains(ARROW)
-        .then(|| line.split(ARROW).last())
-        .flatten()
-        .map(|part| PathBuf::from(part.trim()))
-        .filter(|path| path.exists())
-}
+fn main() -> Result<()> {
+    let mut args = std::env::args_os();
+    let program = args.next().unwrap_or_default();
+    let mut args = expand_response_files(args, 0)?;
 
-fn collect_files<F>(current: &Path, base: &Path, files: &mut Vec<File>, filter: F) -> Result<()>
-where
-    F: Fn(&Path) -> bool + Copy,
-{
-    if !current.exists() {
-        return Ok(());
+    if let Some(dir) = take_project_dir(&mut args) {
+        std::env::set_current_dir(&dir)
+            .with_context(|| format!("Failed to enter project directory: {}", dir.display()))?;
     }
 
-    let metadata = fs::symlink_metadata(current)?;
+    let (cli, profile_found) = parse(&program, args.clone(), None)?;
 
-    if metadata.is_dir() {
-        for entry in fs::read_dir(current)? {
-            collect_files(&entry?.path(), base, files, filter)?;
+    if let Some(selection) = pack_args(&cli).and_then(pack::PackArgs::workspace_selection) {
+        // Stdout holds a single JSON array of the summaries of all mods.
+        let json = pack_args(&cli).is_some_and(pack::PackArgs::json);
+        if json {
+            pack::collect_summaries();
         }
-    } else if filter(current) {
-        let target = current
-            .strip_prefix(base)
-            .with_context(|| format!("Failed to strip prefix: {}", current.display()))?;
-
-        files.push(File {
-            source: current.to_path_buf(),
-            target: target.to_path_buf(),
+        let result = workspace::run(selection, json, |shared| {
+            let (cli, profile_found) = parse(&program, args.clone(), shared)?;
+            check_profile(&cli, profile_found)?;
+            run(&cli)
         });
+        pack::print_collected_summaries()?;
+        return result;
     }
 
-    Ok(())
+    check_profile(&cli, profile_found)?;
+    run(&cli)
 }
 
-fn collect_assets_and_include(
-    assets: &Vec<String>,
-    include: &Vec<String>,
-    files: &mut Vec<File>,
-) -> Result<()> {
-    for dir in assets {
-        let path = Path::new(dir);
-        collect_files(path, path, files, |_| true)?;
-    }
-
-    for file in include {
-        let source = PathBuf::from(file);
-        let target = source.file_name().map(PathBuf::from).unwrap_or_default();
-        files.push(File { source, target });
+/// Options of the packing commands, which config files apply to.
+fn pack_args(cli: &Cli) -> Option<&pack::PackArgs> {
+    match &cli.command {
+        None => Some(&cli.pack),
+        Some(Commands::Pack(args) | Commands::Validate(args)) => Some(args),
+        Some(_) => None,
     }
-
-    Ok(())
 }
 
-fn generate_output_path(output: &Option<String>, files: &[File]) -> Result<PathBuf> {
-    let output = if let Some(output) = output {
-        PathBuf::from(output)
-    } else {
-        let mod_json =
-            find_file(files, "mod.json").with_context(|| "Failed to find 'mod.json' in assets")?;
-
-        let content = fs::read_to_string(&mod_json)
-            .with_context(|| format!("Failed to read: {}", mod_json.display()))?;
-
-        let mod_struct: Mod = serde_json::from_str(&content)
-            .with_context(|| format!("Failed to parse: {}", mod_json.display()))?;
-
-        PathBuf::from("bin")
-            .join("Mod")
-            .join(format!("{}-{}.zip", mod_struct.name, mod_struct.version))
-    };
-
-    if let Some(parent) = output.parent() {
-        if parent.as_os_str().is_empty() || parent.exists() {
-            return Ok(output);
+/// Fails for a '--profile' that no config file defines.
+fn check_profile(cli: &Cli, found: bool) -> Result<()> {
+    match pack_args(cli).and_then(pack::PackArgs::profile) {
+        Some(profile) if !found => {
+            bail!("Unknown profile '{}', no config file defines it", profile)
         }
-        fs::create_dir_all(parent)
-            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+        _ => Ok(()),
     }
-    Ok(output)
 }
 
-fn compile(build: &str, pdb: bool, files: &mut Vec<File>) -> Result<()> {
-    println!("Compiling with: {}\n", build);
-
-    let parts = shlex::split(build).ok_or_else(|| anyhow!("Invalid build command: {}", build))?;
-
-    if parts.is_empty() {
-        bail!("Build command is empty")
+/// Parses the arguments after merging in the options of the project config file,
+/// then those shared by the workspace, each only where not given before.
+/// Also returns whether a config file defines the profile selected with '--profile'.
+fn parse(
+    program: &OsString,
+    mut args: Vec<OsString>,
+    shared: Option<&workspace::Shared>,
+) -> Result<(Cli, bool)> {
+    let mut profile_found = false;
+    if let Some(path) = config::find()? {
+        let table = config::read(&path)?;
+        profile_found |= merge_config(program, &mut args, &path, &table)?;
     }
-
-    let mut child = std::process::Command::new(&parts[0])
-        .args(&parts[1..])
-        .stdout(Stdio::piped())
-        .stderr(Stdio::inherit())
-        .spawn()
-        .with_context(|| format!("Failed to execute build command: {}", build))?;
-
-    let stdout = child.stdout.take().unwrap();
-    let reader = BufReader::new(stdout);
-
-    let mut count = 0;
-    for line in reader.lines() {
-        let line = line?;
-        println!("{}", line);
-
-        if let Some(source) = get_dotnet_build(&line) {
-            let target = source.file_name().map(PathBuf::from).unwrap_or_default();
-            files.push(File {
-                source: source.clone(),
-                target,
-            });
-            count += 1;
-        };
-    }
-
-    if pdb {
-        let mut pdbs = Vec::new();
-        for file in files.iter().skip(files.len() - count) {
-            let source = file.source.with_extension("pdb");
-            if source.exists() {
-                let target = source.file_name().map(PathBuf::from).unwrap_or_default();
-                pdbs.push(File { source, target });
-            }
-        }
-        count += pdbs.len();
-        files.extend(pdbs);
-    }
-
-    println!();
-
-    if count == 0 {
-        bail!("No compiled files found");
-    } else {
-        println!("Compiled {} files", count);
+    if let Some(shared) = shared {
+        profile_found |= merge_config(program, &mut args, &shared.path, &shared.options)?;
     }
 
-    Ok(())
+    let cli = Cli::parse_from(std::iter::once(program.clone()).chain(args));
+    Ok((cli, profile_found))
 }
 
-fn collect_sources(sources: &[String], files: &mut Vec<File>) -> Result<()> {
-    for source in sources {
-        let path = Path::new(source);
-        if path.exists() {
-            let base = path.parent().unwrap_or_else(|| Path::new("."));
-            collect_files(path, base, files, |p| {
-                p.extension().is_some_and(|e| e.eq_ignore_ascii_case("cs"))
-            })?;
-        }
+/// Inserts the options of a config file before the command line, so the command line overrides them.
+/// Options of the profile selected with '--profile' come first; returns whether the file defines it.
+fn merge_config(
+    program: &OsString,
+    args: &mut Vec<OsString>,
+    path: &Path,
+    table: &toml::Table,
+) -> Result<bool> {
+    let profile = packing_matches(program, args)
+        .and_then(|(_, matches, _)| matches.get_one::<String>("profile").cloned());
+
+    let mut found = false;
+    if let Some(profile) = profile
+        && let Some(options) = config::profile(path, table, &profile)?
+    {
+        splice_config(program, args, path, options)?;
+        found = true;
     }
-
-    Ok(())
+    splice_config(program, args, path, table)?;
+    Ok(found)
 }
 
-fn zip(path: &PathBuf, files: &[File]) -> Result<()> {
-    let file = fs::File::create(path)
-        .with_context(|| format!("Failed to create file: {}", path.display()))?;
-    let mut zip = zip::ZipWriter::new(file);
-    let options = SimpleFileOptions::default();
-
-    for file in files.iter().filter(|f| f.source.exists()) {
-        if file.source.is_dir() {
-            continue;
+/// The packing command the arguments run with its matches, and the index its arguments start at.
+fn packing_matches(program: &OsString, args: &[OsString]) -> Option<(Command, ArgMatches, usize)> {
+    let command = Cli::command();
+    let matches = command
+        .clone()
+        .get_matches_from(std::iter::once(program.clone()).chain(args.iter().cloned()));
+
+    match matches.subcommand() {
+        None => Some((command, matches, 0)),
+        Some((name @ ("pack" | "validate" | "watch"), matches)) => {
+            Some((command.find_subcommand(name)?.clone(), matches.clone(), 1))
         }
-
-        let path = file.target.to_string_lossy().replace('\\', "/");
-        zip.start_file(path, options)?;
-
-        let mut content = fs::File::open(&file.source)
-            .with_context(|| format!("Failed to open: {}", file.source.display()))?;
-        std::io::copy(&mut content, &mut zip)?;
+        Some(_) => None,
     }
+}
 
-    zip.finish()?;
+/// Inserts the options of a config table not given by the arguments.
+fn splice_config(
+    program: &OsString,
+    args: &mut Vec<OsString>,
+    path: &Path,
+    table: &toml::Table,
+) -> Result<()> {
+    let Some((command, matches, start)) = packing_matches(program, args) else {
+        return Ok(());
+    };
+    let config = config::args(path, table, &command, &matches)?;
+    args.splice(start..start, config);
     Ok(())
 }
 
-fn print_packed_message(output: &PathBuf) -> Result<()> {
-    let output = absolute(output)
-        .context("Failed to absolute path")?
-        .display()
-        .to_string();
-
-    println!(
-        "Packed mod at: \x1b]8;;file://{}\x1b\\{}\x1b]8;;\x1b\\",
-        output.replace('\\', "/"),
-        output
-    );
-
-    Ok(())
+fn run(cli: &Cli) -> Result<()> {
+    match &cli.command {
+        None => pack::run(&cli.pack).map(|_| ()),
+        Some(Commands::Pack(args)) => pack::run(args).map(|_| ()),
+        Some(Commands::Validate(args)) => pack::validate(args),
+        Some(Commands::Inspect(args)) => inspect::run(args),
+        Some(Commands::Clean(args)) => clean::run(args),
+        Some(Commands::Init(args)) => init::run(args),
+        Some(Commands::Install(args)) => install::run(args),
+        Some(Commands::Watch(args)) => watch::run(args),
+        Some(Commands::Bump(args)) => bump::run(args),
+        Some(Commands::Verify(args)) => signing::run(args),
+        Some(Commands::Publish(args)) => publish::run(args),
+        Some(Commands::Extract(args)) => extract::run(args),
+        Some(Commands::Diff(args)) => diff::run(args),
+    }
 }
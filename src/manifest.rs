@@ -0,0 +1,118 @@
+use crate::signing::{self, to_hex, with_suffix};
+use anyhow::{Context, Result, bail};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Computes the SHA-256 of every file entry in the archive, keyed by entry name.
+pub fn checksums(archive: &Path) -> Result<BTreeMap<String, String>> {
+    let file = fs::File::open(archive)
+        .with_context(|| format!("Failed to open: {}", archive.display()))?;
+    let mut archive = zip::ZipArchive::new(file)
+        .with_context(|| format!("Failed to read archive: {}", archive.display()))?;
+
+    let mut checksums = BTreeMap::new();
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        if entry.is_dir() {
+            continue;
+        }
+
+        let name = entry.name().to_string();
+        if name.contains('\n') {
+            bail!("Entry name contains a line break: {:?}", name);
+        }
+
+        let mut hasher = Sha256::new();
+        std::io::copy(&mut entry, &mut hasher)
+            .with_context(|| format!("Failed to read entry: {}", name))?;
+        checksums.insert(name, to_hex(&hasher.finalize()));
+    }
+
+    Ok(checksums)
+}
+
+/// Serializes checksums in the canonical form that is signed.
+/// Each line is '<sha256>  <entry>' terminated by '\n', sorted by entry name.
+/// Header lines '# <key>: <value>' come first and are covered by the signature, but not checked against the archive.
+fn serialize(header: &[(&str, String)], checksums: &BTreeMap<String, String>) -> String {
+    let header = header
+        .iter()
+        .map(|(key, value)| format!("# {}: {}\n", key, value.replace(['\r', '\n'], " ")));
+    let checksums = checksums
+        .iter()
+        .map(|(name, hash)| format!("{}  {}\n", hash, name));
+    header.chain(checksums).collect()
+}
+
+/// Writes '<archive>.manifest' and '<archive>.manifest.sig', returning the hex public key.
+pub fn sign(archive: &Path, key: &Path, header: &[(&str, String)]) -> Result<String> {
+    let key = signing::signing_key(key)?;
+    let manifest = serialize(header, &checksums(archive)?);
+
+    let manifest_path = with_suffix(archive, ".manifest");
+    fs::write(&manifest_path, &manifest)
+        .with_context(|| format!("Failed to write: {}", manifest_path.display()))?;
+
+    let signature_path = with_suffix(archive, ".manifest.sig");
+    signing::write_signature(&key, &manifest_path, manifest.as_bytes(), &signature_path)?;
+
+    Ok(key.public_hex())
+}
+
+/// Writes '<archive>.sha256' in the format of 'sha256sum', returning the hex digest.
+pub fn write_checksum(archive: &Path) -> Result<String> {
+    let mut file = fs::File::open(archive)
+        .with_context(|| format!("Failed to open: {}", archive.display()))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)
+        .with_context(|| format!("Failed to read: {}", archive.display()))?;
+    let digest = to_hex(&hasher.finalize());
+
+    let name = archive
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    let path = with_suffix(archive, ".sha256");
+    fs::write(&path, format!("{}  {}\n", digest, name))
+        .with_context(|| format!("Failed to write: {}", path.display()))?;
+
+    Ok(digest)
+}
+
+/// Checks the manifest signature, then every entry of the archive against it.
+/// Returns the number of verified entries.
+pub fn verify(archive: &Path, public_key: &Path) -> Result<usize> {
+    let key = signing::verifying_key(public_key)?;
+
+    let manifest_path = with_suffix(archive, ".manifest");
+    let manifest = fs::read_to_string(&manifest_path)
+        .with_context(|| format!("Failed to read: {}", manifest_path.display()))?;
+
+    let signature_path = with_suffix(archive, ".manifest.sig");
+    signing::check_signature(&key, &manifest_path, manifest.as_bytes(), &signature_path)?;
+
+    let mut expected = BTreeMap::new();
+    // Header lines start with '#', which no checksum does.
+    for line in manifest.lines().filter(|line| !line.starts_with('#')) {
+        let (hash, name) = line
+            .split_once("  ")
+            .with_context(|| format!("Invalid manifest line: {}", line))?;
+        expected.insert(name.to_string(), hash.to_string());
+    }
+
+    let actual = checksums(archive)?;
+    for (name, hash) in &expected {
+        match actual.get(name) {
+            Some(actual) if actual == hash => {}
+            Some(_) => bail!("Checksum mismatch: {}", name),
+            None => bail!("Missing entry: {}", name),
+        }
+    }
+    if let Some(name) = actual.keys().find(|name| !expected.contains_key(*name)) {
+        bail!("Entry not listed in manifest: {}", name);
+    }
+
+    Ok(actual.len())
+}
@@ -0,0 +1,112 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Name of the ignore file honored in the project root and in collected directories.
+pub const FILE_NAME: &str = ".nmlignore";
+
+#[derive(Clone)]
+struct Rule {
+    /// Directory containing the ignore file, patterns are relative to it.
+    base: PathBuf,
+    pattern: glob::Pattern,
+    negated: bool,
+    dir_only: bool,
+}
+
+/// Exclusion rules read from '.nmlignore' files, using gitignore syntax.
+#[derive(Clone, Default)]
+pub struct IgnoreRules {
+    rules: Vec<Rule>,
+}
+
+/// Drops '.' components so relative paths compare by their names only.
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|c| !matches!(c, Component::CurDir))
+        .collect()
+}
+
+fn parse_line(base: &Path, line: &str) -> Result<Option<Rule>> {
+    let line = line.trim_end();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+
+    let (negated, line) = match line.strip_prefix('!') {
+        Some(line) => (true, line),
+        None => (false, line.strip_prefix('\\').unwrap_or(line)),
+    };
+    let (dir_only, line) = match line.strip_suffix('/') {
+        Some(line) => (true, line),
+        None => (false, line),
+    };
+
+    // A pattern without an inner slash matches at any depth, one with a slash is anchored.
+    let pattern = match line.strip_prefix('/') {
+        Some(anchored) => anchored.to_string(),
+        None if line.contains('/') => line.to_string(),
+        None => format!("**/{}", line),
+    };
+
+    let pattern = glob::Pattern::new(&pattern)
+        .with_context(|| format!("Invalid ignore pattern: {}", line))?;
+    Ok(Some(Rule {
+        base: base.to_path_buf(),
+        pattern,
+        negated,
+        dir_only,
+    }))
+}
+
+impl IgnoreRules {
+    /// Returns these rules extended by the ignore file in the given directory, if any.
+    pub fn with_dir(&self, dir: &Path) -> Result<IgnoreRules> {
+        let path = dir.join(FILE_NAME);
+        if !path.is_file() {
+            return Ok(self.clone());
+        }
+
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read: {}", path.display()))?;
+
+        let base = normalize(dir);
+        let mut rules = self.clone();
+        for line in content.lines() {
+            if let Some(rule) = parse_line(&base, line)
+                .with_context(|| format!("Failed to parse: {}", path.display()))?
+            {
+                rules.rules.push(rule);
+            }
+        }
+        Ok(rules)
+    }
+
+    /// Whether the path is ignored, the last matching rule deciding.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let path = normalize(path);
+        if path.file_name().is_some_and(|name| name == FILE_NAME) {
+            return true;
+        }
+
+        let options = glob::MatchOptions {
+            require_literal_separator: true,
+            ..Default::default()
+        };
+
+        let mut ignored = false;
+        for rule in &self.rules {
+            if rule.dir_only && !is_dir {
+                continue;
+            }
+            let Ok(relative) = path.strip_prefix(&rule.base) else {
+                continue;
+            };
+            let relative = relative.to_string_lossy().replace('\\', "/");
+            if rule.pattern.matches_with(&relative, options) {
+                ignored = !rule.negated;
+            }
+        }
+        ignored
+    }
+}
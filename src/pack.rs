@@ -0,0 +1,4779 @@
+use crate::build::{BuildAdapter, BuildSystem, Capture};
+use crate::console::{self, Level, error, info, trace, verbose, warning};
+use crate::nmlignore::IgnoreRules;
+use crate::{build, git, license, manifest, pe, signing, time, workspace};
+use anyhow::{Context, Result, anyhow, bail};
+use clap::{ArgAction, Args, ValueEnum};
+use flate2::Compression;
+use flate2::write::DeflateEncoder;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io::{BufRead, BufReader, ErrorKind, IsTerminal, Seek, Write};
+use std::path::{Component, Path, PathBuf, absolute};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, Once, mpsc};
+use std::time::{Duration, Instant, SystemTime};
+use zip::CompressionMethod;
+use zip::write::SimpleFileOptions;
+
+#[derive(Args, Clone)]
+pub struct PackArgs {
+    /// Whether to pack every member of the workspace in 'nmlpacker.workspace.toml'.
+    #[arg(
+        long,
+        conflicts_with_all = ["member", "recursive"],
+        help = "Whether to pack every member of the workspace"
+    )]
+    all: bool,
+
+    /// Whether to succeed even when '--keep-going' skipped files.
+    #[arg(
+        long,
+        requires = "keep_going",
+        help = "Whether to succeed even when '--keep-going' skipped files"
+    )]
+    allow_partial: bool,
+
+    /// Glob patterns locating build artifacts after the build.
+    /// Matches are included alongside the files reported in the build output.
+    #[arg(long, help = "Glob patterns locating build artifacts after the build")]
+    artifact_glob: Vec<String>,
+
+    /// Asset directories to be included in the package.
+    /// Wildcard patterns such as 'packs/*/assets' are expanded relative to the project root.
+    #[arg(long, default_values = &["assets"], help = "Asset directories to be included in the package"
+    )]
+    assets: Vec<String>,
+
+    /// The command used to build the project.
+    /// Defaults to 'dotnet build -p:DebugType=Portable', or 'msbuild -restore -p:DebugType=Portable' for '--build-system msbuild'.
+    #[arg(long, help = "The command used to build the project")]
+    build: Option<String>,
+
+    /// Arguments after '--', appended to the build command verbatim.
+    /// Spares quoting them into '--build', e.g. 'nmlpack -c -- -c Release -p:DefineConstants=WORKSHOP'.
+    #[arg(
+        index = 2,
+        last = true,
+        requires = "compile",
+        value_name = "BUILD_ARGS",
+        help = "Arguments appended to the build command verbatim"
+    )]
+    build_args: Vec<String>,
+
+    /// Directories whose 'obj' folders '--prune-obj' deletes.
+    /// Defaults to the directories of the project and solution files named by the build command, or the working directory.
+    #[arg(
+        long,
+        value_name = "DIR",
+        requires = "prune_obj",
+        help = "Directories whose 'obj' folders '--prune-obj' deletes"
+    )]
+    build_dir: Vec<String>,
+
+    /// Environment variables set for the build command, as 'KEY=VALUE'.
+    /// E.g. '--build-env WorldBoxManaged=/path/to/Managed' for a project referencing the game's assemblies.
+    #[arg(
+        long,
+        value_name = "KEY=VALUE",
+        value_parser = parse_build_env,
+        help = "Environment variable set for the build command, as 'KEY=VALUE'"
+    )]
+    build_env: Vec<(String, String)>,
+
+    /// Whether to embed 'build_info.json' recording the git commit, branch and pack time.
+    /// Traces a package back to its source; git fields are null outside a repository.
+    #[arg(
+        long,
+        help = "Whether to embed 'build_info.json' recording the git commit, branch and pack time"
+    )]
+    build_info: bool,
+
+    /// Path of a file receiving the full stdout and stderr of the build command.
+    /// Kept alongside the console output, so failed CI builds can be diagnosed afterwards.
+    #[arg(
+        long,
+        value_name = "PATH",
+        requires = "compile",
+        help = "Path of a file receiving the full output of the build command"
+    )]
+    build_log: Option<String>,
+
+    /// Directory searched for assemblies written during the build, with '--build-system directory'.
+    #[arg(
+        long,
+        value_name = "DIR",
+        default_value = "bin",
+        help = "Directory searched for assemblies written during the build"
+    )]
+    build_output_dir: String,
+
+    /// Regular expressions matching build output lines that name a built assembly, used with '--capture output'.
+    /// The path is the 'path' group, or the first group; patterns for common MSBuild formats are tried after them.
+    #[arg(
+        long,
+        value_name = "REGEX",
+        help = "Regular expressions matching build output lines that name a built assembly"
+    )]
+    build_output_pattern: Vec<String>,
+
+    /// Tool driving the build, deciding how built assemblies are located.
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = BuildSystem::Dotnet,
+        help = "Tool driving the build, deciding how built assemblies are located"
+    )]
+    build_system: BuildSystem,
+
+    /// Seconds the build command may run before it is killed and the pack fails.
+    /// Guards CI against builds that hang, e.g. on a stalled NuGet restore.
+    #[arg(
+        long,
+        value_name = "SECS",
+        requires = "compile",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Seconds the build command may run before it is killed"
+    )]
+    build_timeout: Option<u64>,
+
+    /// Directory keeping compressed entries between incremental packs.
+    #[arg(
+        long,
+        value_name = "DIR",
+        default_value = "obj/nmlpack-cache",
+        help = "Directory keeping compressed entries between incremental packs"
+    )]
+    cache_dir: String,
+
+    /// How compiled assemblies are located after the build.
+    /// 'properties' asks MSBuild for the 'TargetPath' of the project; 'output' parses 'project -> path' lines printed by the build.
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = Capture::Properties,
+        help = "How compiled assemblies are located after the build"
+    )]
+    capture: Capture,
+
+    /// Whether to fail when the version in 'mod.json' differs from the project or git tag.
+    /// '<Version>' and '<AssemblyVersion>' of project files and a tag on the checked out commit are compared.
+    #[arg(
+        long,
+        help = "Whether to fail when the version in 'mod.json' differs from the project or git tag"
+    )]
+    check_versions: bool,
+
+    /// Checksum written next to each package as '<output>.<algorithm>'.
+    #[arg(
+        long,
+        value_enum,
+        help = "Checksum written next to each package as '<output>.<algorithm>'"
+    )]
+    checksum: Option<Checksum>,
+
+    /// Unix permissions recorded for every entry, in octal, e.g. '644'.
+    /// Replaces the zip library's default mode; the host platform does not matter.
+    #[arg(
+        long,
+        value_name = "OCTAL",
+        value_parser = parse_mode,
+        help = "Unix permissions recorded for every entry, in octal"
+    )]
+    chmod: Option<u32>,
+
+    /// Whether to delete previews left in the temporary directory by '--preview'.
+    #[arg(
+        long,
+        help = "Whether to delete previews left in the temporary directory by '--preview'"
+    )]
+    clean_previews: bool,
+
+    /// Whether to build binary.
+    #[arg(short, long, help = "Whether to build binary")]
+    compile: bool,
+
+    /// Compression level of packed entries, from 0 (fastest) to 9 (smallest).
+    /// The compression method's own default is used if not specified.
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(i64).range(0..=9),
+        help = "Compression level of packed entries, from 0 (fastest) to 9 (smallest)"
+    )]
+    compression_level: Option<i64>,
+
+    /// Compression method of entries not matched by a '--compression-rule'.
+    /// 'bzip2' and 'zstd' need the matching cargo feature and may not be readable by NeoModLoader.
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = Method::Deflate,
+        help = "Compression method of entries not matched by a '--compression-rule'"
+    )]
+    compression_method: Method,
+
+    /// Compression method for files with an extension, as '<ext>=<method>', e.g. 'wav=deflate'.
+    /// Already-compressed formats (png, jpg, jpeg, ogg, zip) are stored unless overridden.
+    #[arg(
+        long,
+        value_name = "EXT=METHOD",
+        value_parser = parse_compression_rule,
+        help = "Compression method for files with an extension, as '<ext>=<method>'"
+    )]
+    compression_rule: Vec<(String, Method)>,
+
+    /// Whether to lock the output directory so concurrent packs cannot clobber each other.
+    /// A second pack into the same directory fails immediately while the lock is held.
+    #[arg(
+        long,
+        help = "Whether to lock the output directory so concurrent packs cannot clobber each other"
+    )]
+    concurrency_safe: bool,
+
+    /// Build configuration, e.g. 'Release' or 'Debug', passed to the build command as '-c <config>'.
+    /// '{config}' in the build command and artifact globs is substituted instead where present,
+    /// and '--build-system directory' searches '<build-output-dir>/<config>'.
+    #[arg(
+        long,
+        requires = "compile",
+        conflicts_with = "multi_config",
+        help = "Build configuration passed to the build command, e.g. 'Release'"
+    )]
+    configuration: Option<String>,
+
+    /// Glob patterns of archive entries renamed to include a short content hash.
+    /// A mapping from original to hashed entry names is written to 'asset-manifest.json'.
+    #[arg(
+        long,
+        help = "Glob patterns of archive entries renamed to include a short content hash"
+    )]
+    content_hash_names: Vec<String>,
+
+    /// File name patterns of assemblies never packed from the build, e.g. 'Steamworks*.dll'.
+    /// The game's own, Unity and NeoModLoader assemblies are always left out unless '--no-default-denylist' is given.
+    #[arg(
+        long,
+        value_name = "GLOB",
+        help = "File name patterns of assemblies never packed from the build"
+    )]
+    deny_assembly: Vec<String>,
+
+    /// Whether to collect files without writing the package.
+    /// The entries that would be packed are listed as a tree with their sizes and sources.
+    #[arg(long, help = "Whether to collect files without writing the package")]
+    dry_run: bool,
+
+    /// Whether to embed a lock file recording the versions of packed assemblies.
+    /// Versions are read from the assembly metadata of every packed DLL.
+    #[arg(
+        long,
+        help = "Whether to embed a lock file recording the versions of packed assemblies"
+    )]
+    embed_deps_lock: bool,
+
+    /// Glob patterns restricting the package to matching archive entries.
+    /// Patterns are matched against target paths after all files are collected.
+    #[arg(
+        long,
+        help = "Glob patterns restricting the package to matching archive entries"
+    )]
+    entry: Vec<String>,
+
+    /// Archive targets to write first, in the given order.
+    /// Useful for consumers that read the archive sequentially, e.g. 'mod.json,icon.png'.
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "Archive targets to write first, in the given order"
+    )]
+    entry_order: Vec<String>,
+
+    /// Whether to add the output directory to the project's '.gitignore'.
+    /// The file is created when absent; an existing matching line is never duplicated.
+    #[arg(
+        long,
+        help = "Whether to add the output directory to the project's '.gitignore'"
+    )]
+    ensure_gitignore: bool,
+
+    /// Glob patterns of files to leave out of the package, e.g. '**/*.psd'.
+    /// Patterns are matched against target paths after all files are collected.
+    #[arg(long, help = "Glob patterns of files to leave out of the package")]
+    exclude: Vec<String>,
+
+    /// Whether to fail the pack when the build emits compiler or MSBuild warnings.
+    /// Keeps releases warning-clean without setting 'TreatWarningsAsErrors' in the project.
+    #[arg(
+        long,
+        requires = "compile",
+        help = "Whether to fail the pack when the build emits warnings"
+    )]
+    fail_on_warnings: bool,
+
+    /// Folder to place compiled dependency assemblies in.
+    /// The assembly collected last is treated as the mod itself and stays at the root.
+    #[arg(long, help = "Folder to place compiled dependency assemblies in")]
+    flat_deps: Option<String>,
+
+    /// Whether to place the files of included directories by their file name, like included files.
+    /// Without it, included directories keep their structure below the directory name or target.
+    #[arg(
+        long,
+        help = "Whether to place the files of included directories by their file name"
+    )]
+    flatten: bool,
+
+    /// Whether to overwrite an existing package with different contents.
+    /// Without it, packing fails rather than replacing a package that may already be published.
+    #[arg(
+        long,
+        help = "Whether to overwrite an existing package with different contents"
+    )]
+    force: bool,
+
+    /// Pre-staged directory packed verbatim with its contents at the archive root.
+    /// Replaces '--assets', '--include' and '--sources'; 'mod.json' is read from inside it.
+    #[arg(
+        long,
+        value_name = "DIR",
+        conflicts_with_all = ["assets", "include", "sources"],
+        help = "Pre-staged directory packed verbatim with its contents at the archive root"
+    )]
+    from_directory: Option<String>,
+
+    /// Additional files or directories to include, wildcard patterns such as 'libs/*.dll' allowed.
+    /// 'source:target' places the file at 'target' inside the archive, a folder when it ends with '/'.
+    /// Default values are provided for forward compatibility with existing mod structures.
+    #[arg(long, default_values = &["Locals", "LICENSE", "default_config.json", "icon.png", "mod.json"], help = "Additional files or directories to include, 'source:target' to place one in the archive")]
+    include: Vec<String>,
+
+    /// Whether to pack project files found in source directories along with the code.
+    /// By default only '.cs' files are packed; this adds '.csproj', '.sln' and '.user' files.
+    #[arg(
+        long,
+        help = "Whether to pack project files found in source directories along with the code"
+    )]
+    include_project_files: bool,
+
+    /// Whether to reuse the compressed data of entries unchanged since the last pack.
+    /// Entries are keyed by the hash of their name, content and options in '--cache-dir'.
+    #[arg(
+        long,
+        help = "Whether to reuse the compressed data of entries unchanged since the last pack"
+    )]
+    incremental: bool,
+
+    /// Archive path of a generated index listing each file's source modification time and size.
+    /// The index is tab-separated and sorted by entry path.
+    #[arg(
+        long,
+        value_name = "NAME",
+        help = "Archive path of a generated index listing each file's source modification time and size"
+    )]
+    index: Option<String>,
+
+    /// Number of threads compressing entries, the number of available CPUs if not specified.
+    /// Entries are still written in the same order, so packages do not depend on it.
+    #[arg(
+        short,
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..),
+        help = "Number of threads compressing entries, the number of CPUs if not specified"
+    )]
+    jobs: Option<u32>,
+
+    /// Whether to print a JSON summary of the pack to stdout instead of progress output.
+    /// Warnings and errors are still written to stderr.
+    #[arg(
+        long,
+        conflicts_with = "json_schema",
+        help = "Whether to print a JSON summary of the pack to stdout instead of progress output"
+    )]
+    json: bool,
+
+    /// Whether to print the JSON Schema of 'mod.json' and exit.
+    /// The schema is generated from the same structure used to read 'mod.json'.
+    #[arg(long, help = "Whether to print the JSON Schema of 'mod.json' and exit")]
+    json_schema: bool,
+
+    /// Number of the most recent packages of the mod to keep in the output directory.
+    /// Older '<name>-<version>.zip' files are deleted after packing, with their checksums and signatures.
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..),
+        help = "Number of the most recent packages of the mod to keep in the output directory"
+    )]
+    keep: Option<u32>,
+
+    /// Whether to skip files that cannot be read instead of aborting the pack.
+    /// Every skipped file is listed at the end and the pack fails unless '--allow-partial' is given.
+    #[arg(
+        long,
+        help = "Whether to skip files that cannot be read instead of aborting the pack"
+    )]
+    keep_going: bool,
+
+    /// Whether to add a 'LICENSE.spdx' entry with the SPDX identifier of the detected license.
+    #[arg(
+        long,
+        help = "Whether to add a 'LICENSE.spdx' entry with the SPDX identifier of the detected license"
+    )]
+    license_spdx: bool,
+
+    /// Whether '--restore' fails instead of updating an outdated 'packages.lock.json'.
+    #[arg(
+        long,
+        requires = "restore",
+        help = "Whether '--restore' fails instead of updating an outdated 'packages.lock.json'"
+    )]
+    locked_mode: bool,
+
+    /// Whether to record the build command, packer version, pack time and git commit in the signed manifest.
+    /// They are '# key: value' lines ahead of the checksums, which keep their format.
+    #[arg(
+        long,
+        requires = "signed_manifest",
+        help = "Whether to record build info in the signed manifest"
+    )]
+    manifest_include_build_info: bool,
+
+    /// Workspace member to pack, by its path in 'nmlpacker.workspace.toml' or its directory name.
+    #[arg(
+        index = 1,
+        help = "Workspace member to pack, by its path or directory name"
+    )]
+    member: Option<String>,
+
+    /// Whether to merge compiled dependency assemblies into the mod's assembly with ILRepack.
+    /// The package then holds a single assembly; the assembly collected last is treated as the mod.
+    #[arg(
+        long,
+        requires = "compile",
+        conflicts_with = "flat_deps",
+        help = "Whether to merge compiled dependency assemblies into the mod's assembly"
+    )]
+    merge_deps: bool,
+
+    /// Command running ILRepack for '--merge-deps', e.g. 'mono tools/ILRepack.exe'.
+    #[arg(
+        long,
+        value_name = "COMMAND",
+        default_value = "ilrepack",
+        help = "Command running ILRepack for '--merge-deps'"
+    )]
+    merge_tool: String,
+
+    /// Build configurations to compile and pack side by side, e.g. 'Debug,Release'.
+    /// Each configuration's artifacts are packed under '<config>/'; '{config}' in the build command and artifact globs is substituted.
+    #[arg(
+        long,
+        value_delimiter = ',',
+        requires = "compile",
+        help = "Build configurations to compile and pack side by side"
+    )]
+    multi_config: Vec<String>,
+
+    /// Prefix prepended to the generated file name.
+    /// Ignored when '--output' is specified.
+    #[arg(long, help = "Prefix prepended to the generated file name")]
+    name_prefix: Option<String>,
+
+    /// Suffix appended to the generated file name.
+    /// Ignored when '--output' is specified.
+    #[arg(long, help = "Suffix appended to the generated file name")]
+    name_suffix: Option<String>,
+
+    /// Whether to skip capturing build artifacts from the build output.
+    /// Artifacts are then located solely through '--artifact-glob'.
+    #[arg(
+        long,
+        requires = "artifact_glob",
+        help = "Whether to skip capturing build artifacts from the build output"
+    )]
+    no_default_capture: bool,
+
+    /// Whether to pack game, Unity and NeoModLoader assemblies the build copies.
+    /// Shipping them breaks the game, so they are left out by default.
+    #[arg(
+        long,
+        requires = "compile",
+        help = "Whether to pack game, Unity and NeoModLoader assemblies the build copies"
+    )]
+    no_default_denylist: bool,
+
+    /// Glob patterns of JSON entries re-serialized with sorted keys and consistent indentation.
+    /// Malformed JSON is packed unchanged with a warning, or is an error under '--strict'.
+    #[arg(
+        long,
+        help = "Glob patterns of JSON entries re-serialized with sorted keys and consistent indentation"
+    )]
+    normalize_json: Vec<String>,
+
+    /// Policy for files that would be packed at the same archive path.
+    /// 'rename' appends a number to the later path, e.g. 'file (1).png'. Files with identical content are always packed once.
+    /// Entries the pack generates, such as the build info, never share a path with a packed file.
+    #[arg(
+        long,
+        visible_alias = "on-conflict",
+        value_enum,
+        default_value_t = Collision::Error,
+        help = "Policy for files that would be packed at the same archive path"
+    )]
+    on_collision: Collision,
+
+    /// Whether to reveal the package in the file manager after packing.
+    /// Skipped in CI and other non-interactive environments.
+    #[arg(
+        long,
+        help = "Whether to reveal the package in the file manager after packing"
+    )]
+    open: bool,
+
+    /// Maximum length in characters of an archive entry path.
+    /// Longer paths fail to extract with some tools and filesystems; exceeding it is a warning, or an error under '--strict'.
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 200,
+        help = "Maximum length in characters of an archive entry path"
+    )]
+    max_path_length: usize,
+
+    /// Archive target of 'mod.json'.
+    /// By default 'mod.json' is placed at the archive root wherever its source lives.
+    #[arg(long, help = "Archive target of 'mod.json'")]
+    mod_json_target: Option<String>,
+
+    /// The final output path of the packed zip file.
+    /// If not specified, it defaults to the 'bin/Mod/<name>-<version>.zip'.
+    /// Placeholders '{name}', '{version}', '{date}' and '{git_hash}' are replaced.
+    #[arg(short, long, help = "The final output path of the packed zip file")]
+    output: Option<String>,
+
+    /// Whether to include PDB files, '--pdb false' leaves them out.
+    #[arg(
+        long,
+        value_name = "BOOL",
+        num_args = 0..=1,
+        default_value_t = true,
+        default_missing_value = "true",
+        action = ArgAction::Set,
+        help = "Whether to include PDB files, '--pdb false' leaves them out"
+    )]
+    pdb: bool,
+
+    /// Shell commands run after the package is written, e.g. to upload it.
+    /// 'NMLPACKER_OUTPUT', 'NMLPACKER_NAME' and 'NMLPACKER_VERSION' are set for them.
+    #[arg(
+        long,
+        value_name = "COMMAND",
+        help = "Shell commands run after the package is written"
+    )]
+    post_hook: Vec<String>,
+
+    /// Shell commands run before files are collected, e.g. to generate assets.
+    /// 'NMLPACKER_NAME' and 'NMLPACKER_VERSION' are set from 'mod.json' in the project root when present.
+    #[arg(
+        long,
+        value_name = "COMMAND",
+        help = "Shell commands run before files are collected"
+    )]
+    pre_hook: Vec<String>,
+
+    /// Whether to pack into the temporary directory and list the entries.
+    /// Previews are kept for inspection until removed with '--clean-previews'.
+    #[arg(
+        long,
+        conflicts_with = "dry_run",
+        help = "Whether to pack into the temporary directory and list the entries"
+    )]
+    preview: bool,
+
+    /// Profile of the config file to apply, from its '[profile.<name>]' table, e.g. 'release'.
+    /// Its options override the others of the config file, but not the command line.
+    #[arg(long, help = "Profile of the config file to apply, e.g. 'release'")]
+    profile: Option<String>,
+
+    /// Whether to delete intermediate 'obj' directories before building.
+    /// Only directories below '--build-dir' are removed; symlinks are never followed.
+    #[arg(
+        long,
+        requires = "compile",
+        help = "Whether to delete intermediate 'obj' directories before building"
+    )]
+    prune_obj: bool,
+
+    /// Whether to build with 'dotnet publish' and pack the dependency assemblies it copies.
+    /// Ships NuGet dependencies such as 'Newtonsoft.Json' along with the mod, without framework assemblies.
+    #[arg(
+        long,
+        requires = "compile",
+        conflicts_with = "build",
+        help = "Whether to build with 'dotnet publish' and pack the dependency assemblies"
+    )]
+    publish: bool,
+
+    /// Whether to print only warnings and errors.
+    /// A file given to '--tee-output' still receives the full output.
+    #[arg(short, long, help = "Whether to print only warnings and errors")]
+    quiet: bool,
+
+    /// Whether to write byte-for-byte identical packages for identical inputs.
+    /// Entries are sorted by path, timestamps are zeroed and permissions are fixed unless '--chmod' is given.
+    #[arg(
+        long,
+        visible_alias = "deterministic",
+        help = "Whether to write byte-for-byte identical packages for identical inputs"
+    )]
+    reproducible: bool,
+
+    /// Directory searched for mods to pack, every directory below it holding a 'mod.json'.
+    /// Each mod is packed from its own directory; a table of the results follows.
+    #[arg(
+        long,
+        value_name = "DIR",
+        conflicts_with = "member",
+        help = "Directory searched for mods to pack, every directory below it holding a 'mod.json'"
+    )]
+    recursive: Option<String>,
+
+    /// Whether to restore NuGet packages as a step of its own before building.
+    /// Tells restore failures apart from compile failures; uses 'dotnet restore' or 'msbuild -t:Restore'.
+    #[arg(
+        long,
+        requires = "compile",
+        help = "Whether to restore NuGet packages as a step of its own before building"
+    )]
+    restore: bool,
+
+    /// Number of times to retry reading a file after a transient I/O error.
+    /// Missing files and permission errors are never retried.
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 0,
+        help = "Number of times to retry reading a file after a transient I/O error"
+    )]
+    retry_io: u32,
+
+    /// Path of an ed25519 key used to sign the package itself, hex-encoded or a minisign secret key.
+    /// Writes a detached signature to '<output>.sig', checked with the 'verify' command.
+    #[arg(
+        long,
+        value_name = "KEY",
+        help = "Path of an ed25519 key used to write a detached '<output>.sig'"
+    )]
+    sign_key: Option<String>,
+
+    /// Path of an ed25519 key used to sign a checksum manifest of the package, hex-encoded or in minisign format.
+    /// Writes '<output>.manifest' and '<output>.manifest.sig', checked with 'verify --manifest'.
+    #[arg(
+        long,
+        value_name = "KEY",
+        help = "Path of a hex-encoded ed25519 key used to sign a checksum manifest of the package"
+    )]
+    signed_manifest: Option<String>,
+
+    /// Source code directories, wildcard patterns allowed.
+    /// Default values are provided for compatibility with various project layouts.
+    #[arg(long, default_values = &["Code", "code", "src"], help = "Source code directories")]
+    sources: Vec<String>,
+
+    /// Maximum size in bytes of each archive, splitting the package into numbered volumes.
+    /// Every volume is a standalone zip of whole entries named '<output>.001', '<output>.002', etc.
+    #[arg(
+        long,
+        value_name = "BYTES",
+        help = "Maximum size in bytes of each archive, splitting the package into numbered volumes"
+    )]
+    split_size: Option<u64>,
+
+    /// Whether to report uncompressed and compressed sizes of the package.
+    /// Compressed sizes are exact: each file is deflated into a discarding sink.
+    /// The projected archive size adds the zip headers of every entry.
+    #[arg(
+        long,
+        help = "Whether to report uncompressed and compressed sizes of the package"
+    )]
+    stats: bool,
+
+    /// Whether to ensure entries carry no platform-specific extra fields.
+    /// The written archive is read back and rejected if any entry has extra fields, besides the ZIP64 field of entries over 4 GiB.
+    #[arg(
+        long,
+        help = "Whether to ensure entries carry no platform-specific extra fields"
+    )]
+    strip_extra_fields: bool,
+
+    /// Whether to treat packaging warnings as errors.
+    #[arg(long, help = "Whether to treat packaging warnings as errors")]
+    strict: bool,
+
+    /// Path of a file mirroring all console output.
+    /// The file starts with a header recording the invocation and timestamp.
+    #[arg(long, help = "Path of a file mirroring all console output")]
+    tee_output: Option<String>,
+
+    /// Commands transforming matching files before packing, as '<glob>=<command>'.
+    /// '{in}' is replaced with a temporary copy of the file and '{out}' with the path
+    /// the command must write its result to. The glob is matched against archive targets.
+    /// A failing transform packs the original file, or aborts under '--strict'.
+    #[arg(
+        long,
+        help = "Commands transforming matching files before packing, as '<glob>=<command>'"
+    )]
+    transform: Vec<String>,
+
+    /// Whether the pack runs for the 'validate' command, turning 'mod.json' problems into errors.
+    #[arg(skip)]
+    validating: bool,
+
+    /// Detail of progress information, repeat as '-vv' to trace every file.
+    #[arg(
+        short,
+        long,
+        action = ArgAction::Count,
+        conflicts_with = "quiet",
+        help = "Detail of progress information, repeat as '-vv' to trace every file"
+    )]
+    verbose: u8,
+
+    /// Whether to check that the mod assembly is a managed .NET assembly.
+    /// Every packed DLL is inspected; a native mod assembly is a warning, or an error under '--strict'.
+    #[arg(
+        long,
+        help = "Whether to check that the mod assembly is a managed .NET assembly"
+    )]
+    verify_dll_is_managed: bool,
+
+    /// Whether to check the package for intermediate build files such as 'obj' contents.
+    /// Each match is a warning, or an error under '--strict'.
+    #[arg(
+        long,
+        help = "Whether to check the package for intermediate build files such as 'obj' contents"
+    )]
+    verify_no_obj_leak: bool,
+
+    /// Whether to report keys of 'mod.json' that NeoModLoader does not recognize.
+    /// Misspelled keys are otherwise silently ignored; they are errors under '--strict'.
+    #[arg(
+        long,
+        help = "Whether to report keys of 'mod.json' that NeoModLoader does not recognize"
+    )]
+    warn_unknown_mod_json_fields: bool,
+
+    /// Whether to pack the source code under 'Sources/' along with the compiled assemblies.
+    /// Suits open-source mods, and lets NeoModLoader fall back to compiling the sources.
+    #[arg(
+        long,
+        requires = "compile",
+        conflicts_with = "from_directory",
+        help = "Whether to pack the source code under 'Sources/' along with the compiled assemblies"
+    )]
+    with_sources: bool,
+}
+
+impl PackArgs {
+    /// Whether a JSON summary is printed instead of progress output.
+    pub fn json(&self) -> bool {
+        self.json
+    }
+
+    /// Config file profile selected with '--profile'.
+    pub fn profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
+
+    /// Mods selected with '--all', '--recursive' or by name.
+    pub fn workspace_selection(&self) -> Option<workspace::Selection<'_>> {
+        match (&self.member, &self.recursive) {
+            (Some(member), _) => Some(workspace::Selection::Member(member)),
+            (_, Some(dir)) => Some(workspace::Selection::Recursive(dir)),
+            _ if self.all => Some(workspace::Selection::All),
+            _ => None,
+        }
+    }
+
+    /// Paths read by a pack, used to decide when to repack.
+    pub fn inputs(&self) -> Vec<PathBuf> {
+        match &self.from_directory {
+            Some(dir) => vec![PathBuf::from(dir)],
+            None => self
+                .assets
+                .iter()
+                .map(String::as_str)
+                .chain(self.include.iter().map(|include| split_include(include).0))
+                .chain(self.sources.iter().map(String::as_str))
+                .flat_map(|path| match glob_matches(path) {
+                    Ok(Some(matches)) => matches,
+                    _ => vec![path.to_string()],
+                })
+                .map(PathBuf::from)
+                .collect(),
+        }
+    }
+
+    /// Directory receiving the package.
+    pub fn output_dir(&self) -> PathBuf {
+        match &self.output {
+            Some(output) => Path::new(output)
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_default(),
+            None => default_output_dir(),
+        }
+    }
+
+    /// Console detail requested by '--quiet' and '--verbose'.
+    pub fn level(&self) -> Level {
+        match (self.quiet, self.verbose) {
+            (true, _) => Level::Quiet,
+            (false, 0) => Level::Normal,
+            (false, 1) => Level::Verbose,
+            (false, _) => Level::Trace,
+        }
+    }
+
+    /// Returns these options with '--force' set, for packing repeatedly to the same output.
+    pub fn forced(&self) -> PackArgs {
+        let mut args = self.clone();
+        args.force = true;
+        args
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Checksum {
+    /// SHA-256, in the format of 'sha256sum'.
+    Sha256,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Method {
+    /// No compression.
+    Stored,
+    /// Deflate, readable everywhere.
+    Deflate,
+    /// Bzip2, requires the 'bzip2' feature.
+    Bzip2,
+    /// Zstandard, requires the 'zstd' feature.
+    Zstd,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Collision {
+    /// Fail the pack.
+    Error,
+    /// Keep the first file.
+    Skip,
+    /// Keep both, renaming the later file.
+    Rename,
+    /// Keep the last file.
+    Overwrite,
+}
+
+/// Metadata of a NeoModLoader mod, read from 'mod.json'.
+#[derive(Deserialize, JsonSchema)]
+struct Mod {
+    /// Display name of the mod.
+    name: String,
+    /// Version of the mod.
+    version: String,
+    /// Fields not declared above, kept to report misspelled keys.
+    #[serde(flatten)]
+    #[schemars(skip)]
+    extra: BTreeMap<String, serde_json::Value>,
+}
+
+/// Fields of 'mod.json' understood by NeoModLoader, which matches them ignoring case.
+const MOD_JSON_FIELDS: &[&str] = &[
+    "name",
+    "author",
+    "version",
+    "description",
+    "GUID",
+    "iconPath",
+    "RepoUrl",
+    "targetGameBuild",
+    "Dependencies",
+    "OptionalDependencies",
+    "IncompatibleWith",
+];
+
+/// Fields of 'mod.json' written by this tool, ignored by NeoModLoader.
+const TOOL_FIELDS: &[&str] = &["workshopId"];
+
+#[derive(Clone)]
+struct File {
+    pub source: PathBuf,
+    pub target: PathBuf,
+}
+
+struct Transform {
+    pattern: glob::Pattern,
+    command: Vec<String>,
+}
+
+/// A temporary directory removed when dropped.
+pub(crate) struct TempDir(pub(crate) PathBuf);
+
+impl TempDir {
+    pub(crate) fn new(name: &str) -> Result<Self> {
+        let path = std::env::temp_dir().join(format!("nmlpack-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&path)
+            .with_context(|| format!("Failed to create directory: {}", path.display()))?;
+        Ok(Self(path))
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[derive(Clone)]
+struct Generated {
+    pub target: PathBuf,
+    pub content: Vec<u8>,
+}
+
+/// Result of a pack, printed by '--json'.
+#[derive(Serialize)]
+struct Summary {
+    outputs: Vec<PathBuf>,
+    files: usize,
+    size: u64,
+    /// Missing for a dry run, which writes no archive.
+    compressed_size: Option<u64>,
+    /// Missing when nothing was built.
+    build_seconds: Option<f64>,
+    /// Distinct warnings of the build, missing when nothing was built.
+    build_warnings: Option<usize>,
+    /// Digests of the outputs in the same order, with '--checksum sha256'.
+    sha256: Vec<String>,
+    /// SPDX identifier of the included license, missing when none was detected.
+    license: Option<&'static str>,
+    skipped: Vec<PathBuf>,
+    warnings: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct BuildInfo {
+    commit: Option<String>,
+    branch: Option<String>,
+    dirty: Option<bool>,
+    packed_at: String,
+    packer_version: &'static str,
+}
+
+#[derive(Serialize)]
+struct DependencyLock {
+    assemblies: Vec<LockedAssembly>,
+}
+
+#[derive(Serialize)]
+struct LockedAssembly {
+    path: String,
+    name: String,
+    version: String,
+}
+
+const DEPENDENCIES_LOCK: &str = "dependencies.lock.json";
+
+const ASSET_MANIFEST: &str = "asset-manifest.json";
+
+const BUILD_INFO: &str = "build_info.json";
+
+const LICENSE_SPDX: &str = "LICENSE.spdx";
+
+fn parse_mode(value: &str) -> Result<u32, String> {
+    u32::from_str_radix(value, 8)
+        .ok()
+        .filter(|mode| *mode <= 0o7777)
+        .ok_or_else(|| format!("expected an octal mode up to 7777, got '{}'", value))
+}
+
+fn parse_build_env(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("expected 'KEY=VALUE', got '{}'", value)),
+    }
+}
+
+fn parse_compression_rule(value: &str) -> Result<(String, Method), String> {
+    let (extension, method) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected '<ext>=<method>', got '{}'", value))?;
+    let extension = extension.trim().trim_start_matches('.').to_lowercase();
+    if extension.is_empty() {
+        return Err(format!("missing extension in '{}'", value));
+    }
+    Ok((extension, Method::from_str(method.trim(), true)?))
+}
+
+/// Packs the mod, mirroring console output to '--tee-output' when given.
+/// Returns the archives written, none for a dry run.
+pub fn run(cli: &PackArgs) -> Result<Vec<PathBuf>> {
+    console::set_level(if cli.json { Level::Quiet } else { cli.level() });
+
+    if let Some(path) = &cli.tee_output {
+        console::tee(Path::new(path))?;
+    }
+
+    // Warnings of an earlier pack in watch mode belong to that pack's summary.
+    console::take_warnings();
+
+    let result = pack(cli);
+    // A failed step may have left its progress bar running.
+    console::finish_progress();
+    if let Err(e) = &result {
+        console::record(format_args!("Error: {:?}", e));
+    }
+    result
+}
+
+/// Runs every packing step and check without writing the package.
+pub fn validate(cli: &PackArgs) -> Result<()> {
+    let mut cli = cli.clone();
+    cli.dry_run = true;
+    cli.validating = true;
+    run(&cli).map(|_| ())
+}
+
+fn pack(cli: &PackArgs) -> Result<Vec<PathBuf>> {
+    if cli.json_schema {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&schemars::schema_for!(Mod))?
+        );
+        return Ok(Vec::new());
+    }
+
+    if !cli.pre_hook.is_empty() {
+        let mod_json = match &cli.from_directory {
+            Some(dir) => Path::new(dir).join("mod.json"),
+            None => PathBuf::from("mod.json"),
+        };
+        let metadata = read_mod(&mod_json).ok();
+        run_hooks(&cli.pre_hook, &hook_env(metadata.as_ref(), None))?;
+    }
+
+    let mut files = Vec::new();
+
+    console::start_progress("Collecting files", None);
+    if let Some(dir) = &cli.from_directory {
+        let dir = Path::new(dir);
+        if !dir.is_dir() {
+            bail!("Not a directory: {}", dir.display());
+        }
+        collect_files(dir, dir, &mut files, |_| true)?;
+    } else {
+        let assets = expand_globs(&cli.assets, cli.strict)?;
+        let include = expand_includes(&cli.include, cli.flatten, cli.strict)?;
+        collect_assets_and_include(&assets, include, &mut files)?;
+    }
+    console::finish_progress();
+
+    place_mod_json(cli.mod_json_target.as_deref(), &mut files)?;
+    check_mod_json(&files, cli.strict, cli.validating)?;
+
+    if cli.check_versions {
+        check_versions(&files)?;
+    }
+
+    if cli.warn_unknown_mod_json_fields {
+        check_mod_json_fields(&files, cli.strict)?;
+    }
+
+    if cli.clean_previews {
+        clean_previews()?;
+    }
+
+    let output = generate_output_path(
+        &cli.output,
+        cli.name_prefix.as_deref(),
+        cli.name_suffix.as_deref(),
+        &files,
+    )?;
+    let output = if cli.preview {
+        preview_dir().join(output.file_name().unwrap_or_default())
+    } else {
+        output
+    };
+
+    // Merged assemblies are packed from here, so it outlives the build.
+    let merged = if cli.merge_deps {
+        Some(TempDir::new("merge")?)
+    } else {
+        None
+    };
+
+    let mut build_duration = None;
+    let mut build_commands = Vec::new();
+    let mut build_warnings = None;
+    if cli.compile {
+        let build_log = match &cli.build_log {
+            Some(path) => Some(Arc::new(Mutex::new(
+                fs::File::create(path)
+                    .with_context(|| format!("Failed to create build log: {}", path))?,
+            ))),
+            None => None,
+        };
+
+        let denylist = build::assembly_denylist(&cli.deny_assembly, !cli.no_default_denylist)?;
+
+        let process = BuildProcess {
+            env: cli.build_env.clone(),
+            timeout: cli.build_timeout.map(Duration::from_secs),
+            log: build_log,
+        };
+
+        let configs = if cli.multi_config.is_empty() {
+            vec![cli.configuration.as_deref()]
+        } else {
+            cli.multi_config.iter().map(|c| Some(c.as_str())).collect()
+        };
+
+        for (index, config) in configs.iter().enumerate() {
+            if configs[..index].contains(config) {
+                bail!("Configuration listed twice: {}", config.unwrap_or_default());
+            }
+        }
+
+        let extra = shlex::try_join(cli.build_args.iter().map(String::as_str))
+            .context("Invalid build argument")?;
+        let started = Instant::now();
+        let mut pruned = !cli.prune_obj;
+        let mut restored = !cli.restore;
+
+        for config in &configs {
+            let adapter = build::adapter(
+                cli.build_system,
+                cli.capture,
+                &cli.build_output_pattern,
+                &cli.build_output_dir,
+                *config,
+                &cli.build_env,
+                cli.publish,
+            )?;
+            let build = match &cli.build {
+                Some(build) => build.as_str(),
+                None => adapter
+                    .default_command()
+                    .context("'--build-system directory' needs a '--build' command")?,
+            };
+
+            let artifact_globs = cli
+                .artifact_glob
+                .iter()
+                .map(|glob| configure(glob, *config, false))
+                .collect::<Vec<_>>();
+
+            let build = format!("{} {}", configure(build, *config, true), extra);
+            let parts =
+                shlex::split(&build).ok_or_else(|| anyhow!("Invalid build command: {}", build))?;
+            if !pruned {
+                if cli.build_dir.is_empty() {
+                    for dir in build::project_dirs(&parts) {
+                        prune_obj_dirs(&dir)?;
+                    }
+                } else {
+                    for dir in &cli.build_dir {
+                        prune_obj_dirs(Path::new(dir))?;
+                    }
+                }
+                pruned = true;
+            }
+            if !restored {
+                let command = adapter
+                    .restore_command(&parts, cli.locked_mode)
+                    .context("'--restore' needs '--build-system dotnet' or 'msbuild'")?;
+                restore(&command, &process)?;
+                restored = true;
+            }
+
+            build_commands.push(build.trim_end().to_string());
+            let start = files.len();
+            *build_warnings.get_or_insert(0) += compile(
+                build.trim_end(),
+                &process,
+                cli.pdb,
+                (!cli.no_default_capture).then_some(adapter.as_ref()),
+                &artifact_globs,
+                &denylist,
+                &mut files,
+            )?;
+            if let Some(segment) = &cli.flat_deps {
+                flatten_dependencies(segment, &mut files[start..])?;
+            }
+            if let Some(merged) = &merged {
+                let mut built = files.split_off(start);
+                let dir = merged.0.join(config.unwrap_or("build"));
+                merge_dependencies(&cli.merge_tool, cli.pdb, &dir, &mut built)?;
+                files.extend(built);
+            }
+
+            if let (Some(config), false) = (config, cli.multi_config.is_empty()) {
+                let prefix = validate_segment(config)?;
+                for file in &mut files[start..] {
+                    file.target = prefix.join(&file.target);
+                }
+                info!(
+                    "Packed {} artifacts of {} under: {}/",
+                    files.len() - start,
+                    config,
+                    entry_name(&prefix)
+                );
+            }
+        }
+
+        build_duration = Some(started.elapsed());
+
+        let warnings = build_warnings.unwrap_or_default();
+        if warnings > 0 {
+            info!("Build emitted {} warnings", warnings);
+            if cli.fail_on_warnings {
+                bail!(
+                    "Build emitted {} warnings with '--fail-on-warnings'",
+                    warnings
+                );
+            }
+        }
+    }
+
+    if cli.from_directory.is_none() && (!cli.compile || cli.with_sources) {
+        let sources = expand_globs(&cli.sources, cli.strict)?;
+        console::start_progress("Collecting sources", None);
+        let start = files.len();
+        collect_sources(&sources, cli.include_project_files, &mut files)?;
+        console::finish_progress();
+
+        // Kept apart from the assemblies, so NeoModLoader loads the compiled mod.
+        if cli.compile {
+            for file in &mut files[start..] {
+                file.target = Path::new(SOURCES_FOLDER).join(&file.target);
+            }
+        }
+    }
+
+    exclude_entries(&cli.exclude, &mut files)?;
+    filter_entries(&cli.entry, &mut files)?;
+
+    resolve_collisions(cli.on_collision, &mut files, cli.strict)?;
+
+    if cli.compile && cli.pdb {
+        drop_orphan_pdbs(&mut files);
+    }
+
+    let _transformed = if cli.transform.is_empty() {
+        None
+    } else {
+        let temp = TempDir::new("transform")?;
+        apply_transforms(&cli.transform, &mut files, &temp.0, cli.strict)?;
+        Some(temp)
+    };
+
+    let _normalized = if cli.normalize_json.is_empty() {
+        None
+    } else {
+        let temp = TempDir::new("normalize")?;
+        normalize_json(&cli.normalize_json, &mut files, &temp.0, cli.strict)?;
+        Some(temp)
+    };
+
+    let license = check_license(&files, cli.strict)?;
+
+    if cli.verify_dll_is_managed {
+        verify_managed_assemblies(&files, cli.strict)?;
+    }
+
+    if cli.reproducible {
+        files.sort_by_key(|file| entry_name(&file.target));
+    }
+    order_entries(&cli.entry_order, &mut files, cli.strict)?;
+
+    let mut generated = Vec::new();
+
+    if !cli.content_hash_names.is_empty() {
+        generated.push(hash_names(&cli.content_hash_names, &mut files)?);
+    }
+
+    if cli.embed_deps_lock {
+        generated.push(generate_dependencies_lock(&files, cli.strict)?);
+    }
+
+    if cli.build_info {
+        if cli.reproducible {
+            warning!("The build info records the pack time, which differs between packs");
+        }
+        generated.push(generate_build_info()?);
+    }
+
+    if cli.license_spdx {
+        match license {
+            Some(license) => generated.push(Generated {
+                target: PathBuf::from(LICENSE_SPDX),
+                content: format!("SPDX-License-Identifier: {}\n", license).into_bytes(),
+            }),
+            None => warning!("Skipped '{}', no license detected", LICENSE_SPDX),
+        }
+    }
+
+    if let Some(name) = &cli.index {
+        if cli.reproducible {
+            warning!("The index records modification times, which may differ between checkouts");
+        }
+        generated.push(generate_index(name, &files)?);
+    }
+
+    if cli.reproducible {
+        generated.sort_by_key(|entry| entry_name(&entry.target));
+    }
+
+    check_generated_collisions(&files, &generated)?;
+    check_path_lengths(&files, &generated, cli.max_path_length, cli.strict)?;
+
+    if cli.verify_no_obj_leak {
+        check_obj_leaks(&files, cli.strict)?;
+    }
+
+    if cli.stats {
+        print_stats(&files, &generated)?;
+    }
+
+    if cli.dry_run {
+        print_tree(&files, &generated)?;
+        info!("Dry run, skipped writing: {}", output.display());
+
+        if cli.json {
+            let packed = files.iter().filter(|f| f.source.is_file());
+            let mut size = generated.iter().map(|g| g.content.len() as u64).sum();
+            for file in packed.clone() {
+                size += fs::metadata(&file.source)?.len();
+            }
+            print_summary(Summary {
+                outputs: Vec::new(),
+                files: packed.count() + generated.len(),
+                size,
+                compressed_size: None,
+                build_seconds: build_duration.map(|d| d.as_secs_f64()),
+                build_warnings,
+                sha256: Vec::new(),
+                license,
+                skipped: Vec::new(),
+                warnings: console::take_warnings(),
+            })?;
+        }
+        return Ok(Vec::new());
+    }
+
+    create_output_dir(&output)?;
+
+    // Held until packing finishes; the OS releases the lock however the process exits.
+    let _lock = if cli.concurrency_safe {
+        Some(lock_output_dir(&output)?)
+    } else {
+        None
+    };
+
+    if cli.ensure_gitignore && !cli.preview {
+        ensure_gitignore(&output)?;
+    }
+
+    let mut options = EntryOptions::new(
+        cli.compression_method,
+        cli.compression_level,
+        &cli.compression_rule,
+    )?;
+    if cli.reproducible {
+        options.base = options
+            .base
+            .last_modified_time(zip::DateTime::default())
+            .unix_permissions(0o644);
+    }
+    if let Some(mode) = cli.chmod {
+        options.base = options.base.unix_permissions(mode);
+    }
+
+    let entries = files.iter().filter(|f| f.source.is_file()).count() + generated.len();
+    if entries > zip::ZIP64_ENTRY_THR && cli.split_size.is_none() {
+        warning!(
+            "Writing ZIP64 archive for {} entries, older tools may not read it",
+            entries
+        );
+    }
+    console::start_progress("Writing entries", Some(entries as u64));
+    let jobs = match cli.jobs {
+        Some(jobs) => jobs as usize,
+        None => std::thread::available_parallelism().map_or(1, |n| n.get()),
+    };
+    let write = WriteOptions {
+        jobs,
+        retries: cli.retry_io,
+        keep_going: cli.keep_going,
+        force: cli.force || cli.preview,
+        cache: cli.incremental.then(|| EntryCache {
+            dir: PathBuf::from(&cli.cache_dir),
+            used: Mutex::new(HashSet::new()),
+        }),
+    };
+    if let Some(cache) = &write.cache {
+        fs::create_dir_all(&cache.dir)
+            .with_context(|| format!("Failed to create directory: {}", cache.dir.display()))?;
+    }
+    let (archives, skipped) = if let Some(limit) = cli.split_size {
+        write_volumes(&output, &files, &generated, limit, &options, &write)?
+    } else {
+        let skipped = zip(&output, &files, &generated, &options, &write)?;
+        (vec![output.clone()], skipped)
+    };
+    console::finish_progress();
+    if let Some(cache) = &write.cache {
+        cache.prune()?;
+    }
+
+    let mut digests = Vec::new();
+    for archive in &archives {
+        if cli.strip_extra_fields {
+            verify_no_extra_fields(archive)?;
+        }
+
+        if let Some(key) = &cli.signed_manifest {
+            let header = if cli.manifest_include_build_info {
+                manifest_build_info(&build_commands)
+            } else {
+                Vec::new()
+            };
+            let public_key = manifest::sign(archive, Path::new(key), &header)?;
+            info!("Signed manifest with public key: {}", public_key);
+        }
+
+        print_packed_message(archive)?;
+
+        if let Some(Checksum::Sha256) = cli.checksum {
+            let digest = manifest::write_checksum(archive)?;
+            info!("SHA-256: {}", digest);
+            digests.push(digest);
+        }
+
+        if let Some(key) = &cli.sign_key {
+            let (signature, public_key) = signing::sign(archive, Path::new(key))?;
+            info!("Signature: {}", signature.display());
+            info!("Signed with public key: {}", public_key);
+        }
+    }
+
+    if archives.len() > 1 {
+        info!("To reassemble, extract all volumes into the same directory");
+    }
+
+    if !cli.post_hook.is_empty() {
+        let metadata = find_file(&files, "mod.json").and_then(|path| read_mod(&path).ok());
+        run_hooks(&cli.post_hook, &hook_env(metadata.as_ref(), Some(&output)))?;
+    }
+
+    if let (Some(keep), false) = (cli.keep, cli.preview) {
+        let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+        let dir = match output.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let metadata = find_file(&files, "mod.json").and_then(|path| read_mod(&path).ok());
+        match metadata.and_then(|metadata| package_group(&stem, &metadata)) {
+            Some(group) => {
+                prune_packages(dir, keep as usize, Some(&group))?;
+            }
+            None => warning!(
+                "Skipped '--keep': the package name does not contain the mod's name and version"
+            ),
+        }
+    }
+
+    if cli.preview {
+        for archive in &archives {
+            crate::inspect::print_listing(archive)?;
+        }
+    }
+
+    if cli.open {
+        open_in_file_manager(&archives[0])?;
+    }
+
+    if cli.json {
+        let mut summary = Summary {
+            outputs: Vec::new(),
+            files: 0,
+            size: 0,
+            compressed_size: Some(0),
+            build_seconds: build_duration.map(|d| d.as_secs_f64()),
+            build_warnings,
+            sha256: digests,
+            license,
+            skipped: skipped.iter().map(|(source, _)| source.clone()).collect(),
+            warnings: console::take_warnings(),
+        };
+        for archive in &archives {
+            let (files, size, compressed) = archive_totals(archive)?;
+            summary.outputs.push(absolute(archive)?);
+            summary.files += files;
+            summary.size += size;
+            summary.compressed_size = summary.compressed_size.map(|c| c + compressed);
+        }
+        print_summary(summary)?;
+    }
+
+    if !skipped.is_empty() {
+        error!("Skipped {} files:", skipped.len());
+        for (source, reason) in &skipped {
+            error!("  {}: {}", source.display(), reason);
+        }
+        if !cli.allow_partial {
+            bail!("Package is incomplete, {} files skipped", skipped.len());
+        }
+    }
+
+    Ok(archives)
+}
+
+fn find_file(files: &[File], name: &str) -> Option<PathBuf> {
+    files
+        .iter()
+        .filter(|file| file.source.exists())
+        .find(|file| file.source.file_name() == Some(std::ffi::OsStr::new(name)))
+        .map(|file| file.source.clone())
+}
+
+fn place_mod_json(target: Option<&str>, files: &mut [File]) -> Result<()> {
+    let Some(file) = files
+        .iter_mut()
+        .filter(|file| file.source.exists())
+        .find(|file| file.source.file_name() == Some(std::ffi::OsStr::new("mod.json")))
+    else {
+        return Ok(());
+    };
+
+    let target = match target {
+        Some(target) => validate_segment(target)?,
+        None => {
+            if file.target != Path::new("mod.json") {
+                warning!(
+                    "'mod.json' would be packed at {}, placing it at the archive root",
+                    entry_name(&file.target)
+                );
+            }
+            PathBuf::from("mod.json")
+        }
+    };
+
+    file.target = target;
+    Ok(())
+}
+
+fn collect_files<F>(current: &Path, base: &Path, files: &mut Vec<File>, filter: F) -> Result<()>
+where
+    F: Fn(&Path) -> bool + Copy,
+{
+    let ignore = IgnoreRules::default().with_dir(Path::new("."))?;
+    walk_files(current, base, &ignore, files, filter)
+}
+
+/// Collects files below 'current', skipping paths matched by '.nmlignore' files on the way.
+/// The starting path itself was asked for explicitly and is never ignored.
+fn walk_files<F>(
+    current: &Path,
+    base: &Path,
+    ignore: &IgnoreRules,
+    files: &mut Vec<File>,
+    filter: F,
+) -> Result<()>
+where
+    F: Fn(&Path) -> bool + Copy,
+{
+    if !current.exists() {
+        return Ok(());
+    }
+
+    let metadata = fs::symlink_metadata(current)?;
+
+    if metadata.is_dir() {
+        let ignore = ignore.with_dir(current)?;
+        for entry in fs::read_dir(current)? {
+            let entry = entry?;
+            let path = entry.path();
+            if ignore.is_ignored(&path, entry.file_type()?.is_dir()) {
+                verbose!("Ignored: {}", path.display());
+                continue;
+            }
+            walk_files(&path, base, &ignore, files, filter)?;
+        }
+    } else if filter(current) {
+        let target = current
+            .strip_prefix(base)
+            .with_context(|| format!("Failed to strip prefix: {}", current.display()))?;
+        trace!("Collected: {} -> {}", current.display(), entry_name(target));
+        console::advance();
+
+        files.push(File {
+            source: current.to_path_buf(),
+            target: target.to_path_buf(),
+        });
+    } else {
+        trace!("Skipped by filter: {}", current.display());
+    }
+
+    Ok(())
+}
+
+/// Returns the paths matching a wildcard pattern, or 'None' for a literal path.
+fn glob_matches(pattern: &str) -> Result<Option<Vec<String>>> {
+    if !pattern.contains(['*', '?', '[']) {
+        return Ok(None);
+    }
+
+    let mut matches = Vec::new();
+    for path in glob::glob(pattern).with_context(|| format!("Invalid path glob: {}", pattern))? {
+        matches.push(path?.to_string_lossy().into_owned());
+    }
+    Ok(Some(matches))
+}
+
+/// Replaces wildcard patterns with the paths they match, relative to the project root.
+/// Literal paths are kept as given, so missing defaults stay optional.
+fn expand_globs(patterns: &[String], strict: bool) -> Result<Vec<String>> {
+    let mut paths = Vec::new();
+    for pattern in patterns {
+        match glob_matches(pattern)? {
+            Some(matches) if matches.is_empty() => {
+                warn(&format!("Glob matched nothing: {}", pattern), strict)?;
+            }
+            Some(matches) => paths.extend(matches),
+            None => paths.push(pattern.clone()),
+        }
+    }
+    Ok(paths)
+}
+
+/// Splits an include into its source and the archive path after the last ':', if any.
+/// A single letter before the ':' is a Windows drive rather than a source.
+fn split_include(include: &str) -> (&str, Option<&str>) {
+    match include.rsplit_once(':') {
+        Some((source, target)) if source.len() > 1 => (source, Some(target)),
+        _ => (include, None),
+    }
+}
+
+/// Expands includes into the files they name, placed at their file name unless mapped to a path.
+/// Directories are walked and keep their structure below their name or target, unless flattened.
+fn expand_includes(includes: &[String], flatten: bool, strict: bool) -> Result<Vec<File>> {
+    let mut files = Vec::new();
+    for include in includes {
+        let (pattern, target) = split_include(include);
+        let sources = expand_globs(&[pattern.to_string()], strict)?;
+
+        let folder = target.is_some_and(|target| target.ends_with('/'));
+        let target = target.map(validate_segment).transpose()?;
+        if let Some(target) = &target
+            && !folder
+            && sources.len() > 1
+        {
+            bail!(
+                "'{}' matches {} files but maps to the single path {}, end it with '/' to place them in a folder",
+                pattern,
+                sources.len(),
+                entry_name(target)
+            );
+        }
+
+        for source in sources {
+            let source = PathBuf::from(source);
+            let name = source.file_name().map(PathBuf::from).unwrap_or_default();
+
+            if source.is_dir() {
+                let prefix = match &target {
+                    Some(target) if folder && !flatten => target.join(name),
+                    Some(target) => target.clone(),
+                    None if flatten => PathBuf::new(),
+                    None => name,
+                };
+                let mut found = Vec::new();
+                collect_files(&source, &source, &mut found, |_| true)?;
+                for file in &mut found {
+                    if flatten {
+                        file.target = file
+                            .target
+                            .file_name()
+                            .map(PathBuf::from)
+                            .unwrap_or_default();
+                    }
+                    file.target = prefix.join(&file.target);
+                }
+                files.extend(found);
+                continue;
+            }
+
+            let target = match &target {
+                Some(target) if folder => target.join(name),
+                Some(target) => target.clone(),
+                None => name,
+            };
+            files.push(File { source, target });
+        }
+    }
+    Ok(files)
+}
+
+fn collect_assets_and_include(
+    assets: &Vec<String>,
+    include: Vec<File>,
+    files: &mut Vec<File>,
+) -> Result<()> {
+    for dir in assets {
+        let path = Path::new(dir);
+        if path.is_file() {
+            // A single file has no directory to be relative to, so it lands at the root.
+            let target = path.file_name().map(PathBuf::from).unwrap_or_default();
+            files.push(File {
+                source: path.to_path_buf(),
+                target,
+            });
+            continue;
+        }
+        collect_files(path, path, files, |_| true)?;
+    }
+
+    files.extend(include);
+    Ok(())
+}
+
+fn sanitize_file_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+
+    name.trim_end_matches(['.', ' ']).to_string()
+}
+
+fn read_mod(path: &Path) -> Result<Mod> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read: {}", path.display()))?;
+
+    serde_json::from_str(&content).with_context(|| format!("Failed to parse: {}", path.display()))
+}
+
+/// Environment variables describing the package to hook commands.
+fn hook_env(metadata: Option<&Mod>, output: Option<&Path>) -> Vec<(&'static str, String)> {
+    let mut env = Vec::new();
+    if let Some(metadata) = metadata {
+        env.push(("NMLPACKER_NAME", metadata.name.clone()));
+        env.push(("NMLPACKER_VERSION", metadata.version.clone()));
+    }
+    if let Some(output) = output {
+        let output = absolute(output).unwrap_or_else(|_| output.to_path_buf());
+        env.push(("NMLPACKER_OUTPUT", output.display().to_string()));
+    }
+    env
+}
+
+/// Runs each command with the system shell, failing on the first unsuccessful one.
+fn run_hooks(hooks: &[String], env: &[(&str, String)]) -> Result<()> {
+    for hook in hooks {
+        info!("Running hook: {}", hook);
+
+        let mut command = if cfg!(windows) {
+            let mut command = Command::new("cmd");
+            command.arg("/C").arg(hook);
+            command
+        } else {
+            let mut command = Command::new("sh");
+            command.arg("-c").arg(hook);
+            command
+        };
+        let status = command
+            .envs(env.iter().map(|(key, value)| (key, value)))
+            .status()
+            .with_context(|| format!("Failed to execute hook: {}", hook))?;
+
+        if !status.success() {
+            bail!("Hook failed with {}: {}", status, hook);
+        }
+    }
+    Ok(())
+}
+
+fn is_valid_guid(guid: &str) -> bool {
+    !guid.is_empty()
+        && !guid.starts_with('.')
+        && !guid.ends_with('.')
+        && guid
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+}
+
+/// Checks that 'mod.json' has the fields NeoModLoader needs to load the mod, with the right types.
+/// Problems are warnings while packing; '--strict' and the 'validate' command fail on them.
+fn check_mod_json(files: &[File], strict: bool, validating: bool) -> Result<()> {
+    use serde_json::Value;
+
+    let Some(mod_json) = find_file(files, "mod.json") else {
+        return Ok(());
+    };
+
+    let content = fs::read_to_string(&mod_json)
+        .with_context(|| format!("Failed to read: {}", mod_json.display()))?;
+    let value: Value = serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse: {}", mod_json.display()))?;
+    let Some(object) = value.as_object() else {
+        bail!("Expected an object in: {}", mod_json.display());
+    };
+
+    // NeoModLoader matches field names ignoring case.
+    let field = |name: &str| {
+        object
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    };
+
+    let mut problems = Vec::new();
+    for name in ["name", "author", "version", "description"] {
+        match field(name) {
+            None => problems.push(format!("Missing '{}'", name)),
+            Some(Value::String(text)) if text.trim().is_empty() && name != "description" => {
+                problems.push(format!("'{}' is empty", name))
+            }
+            Some(Value::String(_)) => {}
+            Some(_) => problems.push(format!("'{}' must be a string", name)),
+        }
+    }
+
+    if field("targetGameBuild").is_some_and(|build| !build.is_u64()) {
+        problems.push("'targetGameBuild' must be a non-negative integer".to_string());
+    }
+
+    for name in ["Dependencies", "OptionalDependencies", "IncompatibleWith"] {
+        match field(name) {
+            None => {}
+            Some(Value::Array(items)) if items.iter().all(Value::is_string) => {}
+            Some(_) => problems.push(format!("'{}' must be an array of GUID strings", name)),
+        }
+    }
+
+    match field("GUID") {
+        None => {}
+        Some(Value::String(guid)) if is_valid_guid(guid) => {}
+        Some(_) => problems
+            .push("'GUID' must be a string of letters, digits, '.', '_' and '-'".to_string()),
+    }
+
+    for problem in &problems {
+        warn(
+            &format!("{} in: {}", problem, mod_json.display()),
+            strict && !validating,
+        )?;
+    }
+    if validating && !problems.is_empty() {
+        bail!(
+            "Found {} problems in: {}",
+            problems.len(),
+            mod_json.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Drops trailing zero components, so '1.2.0.0' compares equal to '1.2'.
+fn normalize_version(version: &str) -> &str {
+    let mut version = version.trim();
+    while let Some(stripped) = version.strip_suffix(".0") {
+        version = stripped;
+    }
+    version
+}
+
+/// Returns the text of every '<tag>' element in an MSBuild project.
+fn project_elements<'a>(content: &'a str, tag: &str) -> Vec<&'a str> {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+    content
+        .match_indices(&open)
+        .filter_map(|(start, _)| {
+            let value = &content[start + open.len()..];
+            value.find(&close).map(|end| value[..end].trim())
+        })
+        .collect()
+}
+
+/// Compares the version in 'mod.json' with project files and the git tag of the commit.
+fn check_versions(files: &[File]) -> Result<()> {
+    let mod_json =
+        find_file(files, "mod.json").with_context(|| "Failed to find 'mod.json' in assets")?;
+    let version = read_mod(&mod_json)?.version;
+
+    let mut sources = Vec::new();
+    for entry in fs::read_dir(".").context("Failed to read the working directory")? {
+        let path = entry?.path();
+        if !has_extension(&path, "csproj") {
+            continue;
+        }
+
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read: {}", path.display()))?;
+        for tag in ["Version", "AssemblyVersion"] {
+            for value in project_elements(&content, tag) {
+                sources.push((
+                    format!("<{}> in {}", tag, path.display()),
+                    value.to_string(),
+                ));
+            }
+        }
+    }
+
+    match git::exact_tag() {
+        Some(tag) => {
+            let value = tag.strip_prefix('v').unwrap_or(&tag).to_string();
+            sources.push((format!("git tag '{}'", tag), value));
+        }
+        None => verbose!("No git tag on the checked out commit"),
+    }
+
+    let mut mismatches = 0;
+    for (source, value) in &sources {
+        if normalize_version(value) == normalize_version(&version) {
+            verbose!("Version matches {}: {}", source, value);
+        } else {
+            error!(
+                "Version {} is {}, but 'mod.json' has {}",
+                source, value, version
+            );
+            mismatches += 1;
+        }
+    }
+
+    if mismatches > 0 {
+        bail!("Found {} version mismatches", mismatches);
+    }
+    info!("Version {} matches {} sources", version, sources.len());
+
+    Ok(())
+}
+
+fn check_mod_json_fields(files: &[File], strict: bool) -> Result<()> {
+    let Some(mod_json) = find_file(files, "mod.json") else {
+        return Ok(());
+    };
+
+    let mod_struct = read_mod(&mod_json)?;
+    for key in mod_struct.extra.keys() {
+        if !MOD_JSON_FIELDS
+            .iter()
+            .chain(TOOL_FIELDS)
+            .any(|f| f.eq_ignore_ascii_case(key))
+        {
+            warn(&format!("Unknown field in 'mod.json': {}", key), strict)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Directory receiving packages when '--output' is not specified.
+pub fn default_output_dir() -> PathBuf {
+    PathBuf::from("bin").join("Mod")
+}
+
+/// Replaces '{name}', '{version}', '{date}' and '{git_hash}' in the output path.
+/// Values are sanitized so they cannot add directories to the path.
+fn expand_placeholders(template: &str, read_mod_json: impl Fn() -> Result<Mod>) -> Result<String> {
+    let mut expanded = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..]
+            .find('}')
+            .map(|end| start + end)
+            .with_context(|| format!("Unclosed placeholder in output path: {}", template))?;
+
+        let value = match &rest[start + 1..end] {
+            "name" => read_mod_json()?.name,
+            "version" => read_mod_json()?.version,
+            "date" => time::date(),
+            "git_hash" => git::short_hash()
+                .context("'{git_hash}' in the output path needs a git repository")?,
+            other => bail!("Unknown placeholder '{{{}}}' in output path", other),
+        };
+
+        expanded.push_str(&rest[..start]);
+        expanded.push_str(&sanitize_file_name(&value));
+        rest = &rest[end + 1..];
+    }
+    expanded.push_str(rest);
+
+    Ok(expanded)
+}
+
+fn generate_output_path(
+    output: &Option<String>,
+    prefix: Option<&str>,
+    suffix: Option<&str>,
+    files: &[File],
+) -> Result<PathBuf> {
+    let read_mod_json = || {
+        let mod_json =
+            find_file(files, "mod.json").with_context(|| "Failed to find 'mod.json' in assets")?;
+        read_mod(&mod_json)
+    };
+
+    let output = if let Some(output) = output {
+        if output.contains('{') {
+            PathBuf::from(expand_placeholders(output, read_mod_json)?)
+        } else {
+            PathBuf::from(output)
+        }
+    } else {
+        let mod_struct = read_mod_json()?;
+
+        let stem = [
+            prefix,
+            Some(mod_struct.name.as_str()),
+            Some(mod_struct.version.as_str()),
+            suffix,
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join("-");
+
+        let stem = sanitize_file_name(&stem);
+        if stem.is_empty() {
+            bail!("Generated file name is empty");
+        }
+
+        default_output_dir().join(format!("{}.zip", stem))
+    };
+
+    if output.file_name().is_none() {
+        bail!("Output path has no file name: {}", output.display());
+    }
+
+    Ok(output)
+}
+
+fn create_output_dir(output: &Path) -> Result<()> {
+    if output.is_dir() {
+        bail!("Output path is a directory: {}", output.display());
+    }
+
+    let parent = match output.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        // A bare file name is written to the working directory.
+        _ => return Ok(()),
+    };
+
+    if parent.is_dir() {
+        return Ok(());
+    }
+
+    if parent.exists() {
+        bail!("Output parent is not a directory: {}", parent.display());
+    }
+
+    fs::create_dir_all(parent)
+        .with_context(|| format!("Failed to create directory: {}", parent.display()))
+}
+
+const SATELLITE_SUFFIX: &str = ".resources.dll";
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case(extension))
+}
+
+fn is_satellite(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| {
+            name.len() > SATELLITE_SUFFIX.len()
+                && name.is_char_boundary(name.len() - SATELLITE_SUFFIX.len())
+                && name[name.len() - SATELLITE_SUFFIX.len()..]
+                    .eq_ignore_ascii_case(SATELLITE_SUFFIX)
+        })
+}
+
+/// Returns the assembly name, resolving satellite assemblies to the assembly they localize.
+fn assembly_name(path: &Path) -> Option<&str> {
+    let stem = path.file_stem()?.to_str()?;
+    if is_satellite(path) {
+        Some(&stem[..stem.len() - ".resources".len()])
+    } else {
+        Some(stem)
+    }
+}
+
+/// Returns the archive target of a build artifact.
+/// Satellite assemblies keep their locale folder, everything else is placed at the root.
+fn artifact_target(source: &Path) -> PathBuf {
+    let name = source.file_name().map(PathBuf::from).unwrap_or_default();
+    match source.parent().and_then(|parent| parent.file_name()) {
+        Some(locale) if is_satellite(source) => Path::new(locale).join(name),
+        _ => name,
+    }
+}
+
+fn find_satellites(assembly: &Path) -> Result<Vec<File>> {
+    let (Some(dir), Some(stem)) = (assembly.parent(), assembly.file_stem()) else {
+        return Ok(Vec::new());
+    };
+    if !has_extension(assembly, "dll") || is_satellite(assembly) {
+        return Ok(Vec::new());
+    }
+
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let name = format!("{}{}", stem.to_string_lossy(), SATELLITE_SUFFIX);
+
+    let mut satellites = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let source = entry?.path().join(&name);
+        if source.is_file() {
+            satellites.push(File {
+                target: artifact_target(&source),
+                source,
+            });
+        }
+    }
+    satellites.sort_by(|a, b| a.target.cmp(&b.target));
+
+    Ok(satellites)
+}
+
+/// Returns the symbols of the assemblies collected from index 'start' not collected yet.
+fn find_pdbs(files: &[File], start: usize) -> Vec<File> {
+    let mut pdbs = Vec::new();
+    for file in &files[start..] {
+        if !has_extension(&file.source, "dll") || is_satellite(&file.source) {
+            continue;
+        }
+        // Overlapping captures may resolve to the same symbols through different paths.
+        let source = file.source.with_extension("pdb");
+        if source.exists() && !contains_source(files, &source) && !contains_source(&pdbs, &source) {
+            let target = source.file_name().map(PathBuf::from).unwrap_or_default();
+            pdbs.push(File { source, target });
+        }
+    }
+    pdbs
+}
+
+fn contains_source(files: &[File], source: &Path) -> bool {
+    let source = fs::canonicalize(source).unwrap_or_else(|_| source.to_path_buf());
+    files.iter().any(|file| {
+        fs::canonicalize(&file.source).unwrap_or_else(|_| file.source.clone()) == source
+    })
+}
+
+fn collect_artifacts(patterns: &[String], files: &mut Vec<File>) -> Result<usize> {
+    let mut count = 0;
+    for pattern in patterns {
+        let mut matched = false;
+        let paths =
+            glob::glob(pattern).with_context(|| format!("Invalid artifact glob: {}", pattern))?;
+
+        for path in paths {
+            let source = path?;
+            if source.is_dir() {
+                continue;
+            }
+            matched = true;
+
+            if contains_source(files, &source) {
+                continue;
+            }
+
+            let target = artifact_target(&source);
+            files.push(File { source, target });
+            count += 1;
+        }
+
+        if !matched {
+            bail!("Artifact glob matched nothing: {}", pattern);
+        }
+    }
+
+    Ok(count)
+}
+
+fn find_obj_dirs(current: &Path, dirs: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(current)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+
+        let path = entry.path();
+        let name = entry.file_name();
+        if name.eq_ignore_ascii_case("obj") {
+            dirs.push(path);
+        } else if !name.to_string_lossy().starts_with('.') {
+            find_obj_dirs(&path, dirs)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn prune_obj_dirs(root: &Path) -> Result<()> {
+    let root =
+        fs::canonicalize(root).with_context(|| format!("Failed to resolve: {}", root.display()))?;
+
+    let mut dirs = Vec::new();
+    find_obj_dirs(&root, &mut dirs)?;
+
+    for dir in dirs {
+        let resolved = fs::canonicalize(&dir)?;
+        if !resolved.starts_with(&root) || resolved == root {
+            bail!(
+                "Refusing to remove outside of the project: {}",
+                dir.display()
+            );
+        }
+
+        fs::remove_dir_all(&resolved)
+            .with_context(|| format!("Failed to remove: {}", dir.display()))?;
+        info!(
+            "Removed: {}",
+            dir.strip_prefix(&root).unwrap_or(&dir).display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Substitutes '{config}' with the build configuration.
+/// Build commands without the placeholder get the configuration appended as '-c <config>'.
+fn configure(template: &str, config: Option<&str>, append: bool) -> String {
+    let Some(config) = config else {
+        return template.to_string();
+    };
+
+    if template.contains("{config}") {
+        template.replace("{config}", config)
+    } else if append {
+        let config = shlex::try_quote(config).map_or_else(|_| config.into(), |c| c.into_owned());
+        format!("{} -c {}", template, config)
+    } else {
+        template.to_string()
+    }
+}
+
+/// Runs a step of the build, passing every line it prints to stdout on after echoing it.
+/// Its output is also written to '--build-log', and it is killed on timeout or Ctrl+C.
+fn run_build_step(
+    label: &str,
+    command: &str,
+    parts: &[String],
+    process: &BuildProcess,
+    mut on_line: impl FnMut(&str),
+) -> Result<ExitStatus> {
+    handle_interrupt();
+    let mut child = Command::new(&parts[0])
+        .args(&parts[1..])
+        .envs(process.env.iter().map(|(key, value)| (key, value)))
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to execute {}: {}", label.to_lowercase(), command))?;
+
+    let log = process.log.clone();
+    let write_log = move |line: &str| {
+        if let Some(log) = &log {
+            let _ = writeln!(log.lock().unwrap(), "{}", line);
+        }
+    };
+
+    let stderr = child.stderr.take().unwrap();
+    let forward = std::thread::spawn({
+        let write_log = write_log.clone();
+        move || {
+            for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                write_log(&line);
+                error!("{}", line);
+            }
+        }
+    });
+
+    // Lines are read on another thread so a hung build cannot outlast the timeout.
+    let stdout = child.stdout.take().unwrap();
+    let (sender, lines) = mpsc::channel();
+    std::thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            if let Ok(line) = &line {
+                write_log(line);
+            }
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    *BUILD_CHILD.lock().unwrap() = Some(child);
+
+    let deadline = process.timeout.map(|timeout| Instant::now() + timeout);
+    let timed_out = || {
+        kill_build();
+        anyhow!(
+            "{} timed out after {}s: {}",
+            label,
+            process.timeout.unwrap_or_default().as_secs(),
+            command
+        )
+    };
+    loop {
+        let line = match deadline {
+            Some(deadline) => {
+                match lines.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                    Ok(line) => line,
+                    Err(mpsc::RecvTimeoutError::Timeout) => {
+                        console::finish_progress();
+                        return Err(timed_out());
+                    }
+                    Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                }
+            }
+            None => match lines.recv() {
+                Ok(line) => line,
+                Err(_) => break,
+            },
+        };
+        let line = line.inspect_err(|_| kill_build())?;
+        info!("{}", line);
+        on_line(&line);
+    }
+
+    // Polled rather than waited on, so the child stays reachable for Ctrl+C and the timeout.
+    let status = loop {
+        let status = match BUILD_CHILD.lock().unwrap().as_mut() {
+            Some(child) => child
+                .try_wait()
+                .with_context(|| format!("Failed to wait for {}", label.to_lowercase()))?,
+            None => bail!("{} was killed", label),
+        };
+        if let Some(status) = status {
+            BUILD_CHILD.lock().unwrap().take();
+            break status;
+        }
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            console::finish_progress();
+            return Err(timed_out());
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    };
+    let _ = forward.join();
+    Ok(status)
+}
+
+/// Restores NuGet packages ahead of the build, so restore failures are told apart from compile failures.
+fn restore(command: &[String], process: &BuildProcess) -> Result<()> {
+    let display =
+        shlex::try_join(command.iter().map(String::as_str)).context("Invalid restore command")?;
+    info!("Restoring packages with: {}\n", display);
+
+    console::start_progress("Restoring packages", None);
+    let status = run_build_step("Restore command", &display, command, process, |_| {})?;
+    console::finish_progress();
+    if !status.success() {
+        bail!("Restore failed with {}, the build was not started", status);
+    }
+
+    info!("\nRestored packages\n");
+    Ok(())
+}
+
+fn compile(
+    build: &str,
+    process: &BuildProcess,
+    pdb: bool,
+    adapter: Option<&dyn BuildAdapter>,
+    artifact_globs: &[String],
+    denylist: &[glob::Pattern],
+    files: &mut Vec<File>,
+) -> Result<usize> {
+    info!("Compiling with: {}\n", build);
+
+    let parts = shlex::split(build).ok_or_else(|| anyhow!("Invalid build command: {}", build))?;
+
+    if parts.is_empty() {
+        bail!("Build command is empty")
+    }
+
+    let started = SystemTime::now();
+    console::start_progress("Building, captured artifacts", None);
+    let mut count = 0;
+    // MSBuild repeats every warning in the summary at the end of the build.
+    let mut warnings = HashSet::new();
+    let status = run_build_step("Build command", build, &parts, process, |line| {
+        if build::is_warning(line) {
+            warnings.insert(line.trim().to_string());
+        }
+
+        let Some(adapter) = adapter else {
+            return;
+        };
+
+        if let Some(source) = adapter.parse_line(line) {
+            trace!("Build output line names artifact: {}", source.display());
+            if contains_source(files, &source) {
+                trace!("Already collected: {}", source.display());
+                return;
+            }
+            let target = artifact_target(&source);
+            files.push(File {
+                source: source.clone(),
+                target,
+            });
+            count += 1;
+            console::advance();
+        };
+    })?;
+    console::finish_progress();
+    if !status.success() {
+        bail!("Build command failed with {}", status);
+    }
+
+    for source in adapter.map_or(Ok(Vec::new()), |a| a.artifacts(&parts, started))? {
+        verbose!("Build system reports artifact: {}", source.display());
+        if !contains_source(files, &source) {
+            let target = artifact_target(&source);
+            files.push(File { source, target });
+            count += 1;
+        }
+    }
+
+    count += collect_artifacts(artifact_globs, files)?;
+
+    let mut built = files.split_off(files.len() - count);
+    built.retain(|file| {
+        let denied = build::is_denied(&file.source, denylist);
+        if denied {
+            verbose!("Left out denied assembly: {}", file.source.display());
+        }
+        !denied
+    });
+    count = built.len();
+    files.extend(built);
+
+    let mut satellites = Vec::new();
+    for file in files.iter().skip(files.len() - count) {
+        satellites.extend(find_satellites(&file.source)?);
+    }
+    for satellite in satellites {
+        if !contains_source(files, &satellite.source) {
+            files.push(satellite);
+            count += 1;
+        }
+    }
+
+    if pdb {
+        let pdbs = find_pdbs(files, files.len() - count);
+        count += pdbs.len();
+        files.extend(pdbs);
+    }
+
+    info!();
+
+    if count == 0 {
+        bail!("No compiled files found");
+    } else {
+        info!("Compiled {} files", count);
+    }
+
+    Ok(warnings.len())
+}
+
+fn validate_segment(segment: &str) -> Result<PathBuf> {
+    let path = Path::new(segment.trim_matches('/'));
+
+    let valid = !path.as_os_str().is_empty()
+        && path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        && !segment.contains(['\\', '<', '>', ':', '"', '|', '?', '*']);
+
+    if !valid {
+        bail!("Invalid archive path: {}", segment);
+    }
+
+    Ok(path.to_path_buf())
+}
+
+/// Returns the index of the mod assembly, which is the last assembly collected.
+fn primary_assembly(files: &[File]) -> Option<usize> {
+    files
+        .iter()
+        .rposition(|file| has_extension(&file.target, "dll") && !is_satellite(&file.target))
+}
+
+fn flatten_dependencies(segment: &str, files: &mut [File]) -> Result<()> {
+    let folder = validate_segment(segment)?;
+
+    let Some(primary) = primary_assembly(files) else {
+        return Ok(());
+    };
+    let primary = assembly_name(&files[primary].target).map(str::to_string);
+
+    let mut seen = HashMap::new();
+    for file in files.iter_mut() {
+        let assembly = has_extension(&file.target, "dll") || has_extension(&file.target, "pdb");
+
+        if !assembly || assembly_name(&file.target) == primary.as_deref() {
+            continue;
+        }
+
+        let target = folder.join(&file.target);
+        if let Some(previous) = seen.insert(target.clone(), file.source.clone()) {
+            bail!(
+                "Dependency name collision at {}: {} and {}",
+                target.display(),
+                previous.display(),
+                file.source.display()
+            );
+        }
+        file.target = target;
+    }
+
+    Ok(())
+}
+
+/// Merges the dependency assemblies of a build into the mod's assembly with ILRepack,
+/// replacing them with the merged assembly written to the given directory.
+fn merge_dependencies(tool: &str, pdb: bool, dir: &Path, files: &mut Vec<File>) -> Result<()> {
+    let Some(primary) = primary_assembly(files) else {
+        return Ok(());
+    };
+    let primary_name = assembly_name(&files[primary].target).map(str::to_string);
+
+    let is_assembly = |file: &File| {
+        (has_extension(&file.target, "dll") || has_extension(&file.target, "pdb"))
+            && !is_satellite(&file.target)
+    };
+    let dependencies = files
+        .iter()
+        .filter(|file| {
+            has_extension(&file.target, "dll")
+                && is_assembly(file)
+                && assembly_name(&file.target) != primary_name.as_deref()
+        })
+        .collect::<Vec<_>>();
+    if dependencies.is_empty() {
+        return Ok(());
+    }
+
+    let parts = shlex::split(tool)
+        .filter(|parts| !parts.is_empty())
+        .with_context(|| format!("Invalid merge tool: {}", tool))?;
+    fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create directory: {}", dir.display()))?;
+    let output = dir.join(files[primary].source.file_name().unwrap_or_default());
+
+    let mut command = Command::new(&parts[0]);
+    command
+        .args(&parts[1..])
+        .arg(format!("/out:{}", output.display()));
+    if !pdb {
+        command.arg("/ndebug");
+    }
+    // References of the dependencies are resolved next to them.
+    let mut libs = dependencies
+        .iter()
+        .filter_map(|file| file.source.parent())
+        .filter(|lib| !lib.as_os_str().is_empty())
+        .collect::<Vec<_>>();
+    libs.sort();
+    libs.dedup();
+    for lib in libs {
+        command.arg(format!("/lib:{}", lib.display()));
+    }
+    command
+        .arg(&files[primary].source)
+        .args(dependencies.iter().map(|file| &file.source));
+
+    info!(
+        "Merging {} dependencies into: {}",
+        dependencies.len(),
+        entry_name(&files[primary].target)
+    );
+    let result = command
+        .stdin(Stdio::null())
+        .output()
+        .with_context(|| format!("Failed to execute: {}", parts[0]))?;
+    if !result.status.success() {
+        // ILRepack reports errors on stdout.
+        let message = [&result.stderr, &result.stdout]
+            .into_iter()
+            .map(|stream| String::from_utf8_lossy(stream).trim().to_string())
+            .find(|message| !message.is_empty());
+        match message {
+            None => bail!("{} exited with {}", parts[0], result.status),
+            Some(message) => bail!("{} exited with {}: {}", parts[0], result.status, message),
+        }
+    }
+    if !output.is_file() {
+        bail!("{} did not write its output", parts[0]);
+    }
+
+    let merged = dependencies
+        .iter()
+        .filter_map(|file| assembly_name(&file.target).map(str::to_string))
+        .collect::<HashSet<_>>();
+    files.retain(|file| {
+        !is_assembly(file) || !assembly_name(&file.target).is_some_and(|name| merged.contains(name))
+    });
+
+    let symbols = output.with_extension("pdb");
+    files.retain_mut(|file| {
+        if !is_assembly(file) || assembly_name(&file.target) != primary_name.as_deref() {
+            return true;
+        }
+        if has_extension(&file.target, "dll") {
+            file.source = output.clone();
+            return true;
+        }
+        // Symbols of the unmerged assembly no longer match it.
+        file.source = symbols.clone();
+        symbols.is_file()
+    });
+
+    let mut merged = merged.into_iter().collect::<Vec<_>>();
+    merged.sort();
+    verbose!("Merged assemblies: {}", merged.join(", "));
+    Ok(())
+}
+
+/// Archive folder of the sources packed with '--with-sources'.
+const SOURCES_FOLDER: &str = "Sources";
+
+const PROJECT_EXTENSIONS: &[&str] = &["csproj", "sln", "user"];
+
+/// Build output directories below a source root, holding generated code such as 'AssemblyInfo.cs'.
+const BUILD_DIRS: &[&str] = &["bin", "obj"];
+
+fn collect_sources(sources: &[String], project_files: bool, files: &mut Vec<File>) -> Result<()> {
+    for source in sources {
+        let path = Path::new(source);
+        if path.exists() {
+            let base = path.parent().unwrap_or_else(|| Path::new("."));
+            collect_files(path, base, files, |p| {
+                let in_build_dir = p.strip_prefix(path).is_ok_and(|relative| {
+                    relative.components().any(|component| {
+                        BUILD_DIRS
+                            .iter()
+                            .any(|dir| component.as_os_str().eq_ignore_ascii_case(dir))
+                    })
+                });
+                !in_build_dir
+                    && (has_extension(p, "cs")
+                        || project_files && PROJECT_EXTENSIONS.iter().any(|e| has_extension(p, e)))
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+fn entry_name(target: &Path) -> String {
+    target.to_string_lossy().replace('\\', "/")
+}
+
+fn order_entries(order: &[String], files: &mut Vec<File>, strict: bool) -> Result<()> {
+    let mut pinned = Vec::new();
+
+    for target in order {
+        let target = target.replace('\\', "/");
+        let position = files
+            .iter()
+            .position(|file| file.source.is_file() && entry_name(&file.target) == target);
+
+        match position {
+            Some(index) => pinned.push(files.remove(index)),
+            None => warn(
+                &format!("Entry to order first not found: {}", target),
+                strict,
+            )?,
+        }
+    }
+
+    files.splice(0..0, pinned);
+
+    Ok(())
+}
+
+/// Drops symbol files whose assembly was built but is no longer part of the package.
+fn drop_orphan_pdbs(files: &mut Vec<File>) {
+    let assemblies = files
+        .iter()
+        .filter(|file| has_extension(&file.source, "dll"))
+        .map(|file| file.source.with_extension(""))
+        .collect::<Vec<_>>();
+
+    files.retain(|file| {
+        if !has_extension(&file.source, "pdb") {
+            return true;
+        }
+        let stem = file.source.with_extension("");
+        if !stem.with_extension("dll").exists() || assemblies.contains(&stem) {
+            return true;
+        }
+        verbose!(
+            "Skipped symbols of excluded assembly: {}",
+            file.source.display()
+        );
+        false
+    });
+}
+
+fn exclude_entries(patterns: &[String], files: &mut Vec<File>) -> Result<()> {
+    if patterns.is_empty() {
+        return Ok(());
+    }
+
+    let patterns = patterns
+        .iter()
+        .map(|p| glob::Pattern::new(p).with_context(|| format!("Invalid exclude glob: {}", p)))
+        .collect::<Result<Vec<_>>>()?;
+
+    files.retain(|file| {
+        let target = entry_name(&file.target);
+        let excluded = patterns.iter().any(|p| p.matches(&target));
+        if excluded {
+            verbose!("Excluded: {}", target);
+        }
+        !excluded
+    });
+
+    Ok(())
+}
+
+fn filter_entries(patterns: &[String], files: &mut Vec<File>) -> Result<()> {
+    if patterns.is_empty() {
+        return Ok(());
+    }
+
+    let patterns = patterns
+        .iter()
+        .map(|p| glob::Pattern::new(p).with_context(|| format!("Invalid entry glob: {}", p)))
+        .collect::<Result<Vec<_>>>()?;
+
+    files.retain(|file| {
+        let target = entry_name(&file.target);
+        patterns.iter().any(|p| p.matches(&target))
+    });
+
+    let count = files.iter().filter(|f| f.source.is_file()).count();
+    info!("Matched {} entries", count);
+
+    Ok(())
+}
+
+fn file_digest(path: &Path) -> Result<Vec<u8>> {
+    let mut file =
+        fs::File::open(path).with_context(|| format!("Failed to open: {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)
+        .with_context(|| format!("Failed to read: {}", path.display()))?;
+    Ok(hasher.finalize().to_vec())
+}
+
+fn same_content(a: &Path, b: &Path) -> Result<bool> {
+    let size = |path: &Path| {
+        fs::metadata(path)
+            .map(|m| m.len())
+            .with_context(|| format!("Failed to read metadata: {}", path.display()))
+    };
+    Ok(size(a)? == size(b)? && file_digest(a)? == file_digest(b)?)
+}
+
+fn renamed_target(target: &Path, taken: &HashSet<String>) -> PathBuf {
+    let stem = target.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = target.extension().map(|e| e.to_string_lossy());
+
+    (1..)
+        .map(|n| {
+            let name = match &extension {
+                Some(extension) => format!("{} ({}).{}", stem, n, extension),
+                None => format!("{} ({})", stem, n),
+            };
+            target.with_file_name(name)
+        })
+        .find(|candidate| !taken.contains(&entry_name(candidate)))
+        .unwrap_or_default()
+}
+
+fn resolve_collisions(policy: Collision, files: &mut Vec<File>, strict: bool) -> Result<()> {
+    let mut taken = files
+        .iter()
+        .map(|file| entry_name(&file.target))
+        .collect::<HashSet<_>>();
+    let mut kept: HashMap<String, usize> = HashMap::new();
+    let mut removed = HashSet::new();
+
+    for index in 0..files.len() {
+        if !files[index].source.is_file() {
+            continue;
+        }
+
+        let name = entry_name(&files[index].target);
+        let Some(&first) = kept.get(&name) else {
+            kept.insert(name, index);
+            continue;
+        };
+
+        let (earlier, later) = (&files[first].source, &files[index].source);
+        if same_content(earlier, later)? {
+            verbose!("Skipped identical duplicate {}: {}", name, later.display());
+            removed.insert(index);
+            continue;
+        }
+        if !matches!(policy, Collision::Error) {
+            warn(
+                &format!(
+                    "Same archive path {} with DIFFERENT content: {} and {}",
+                    name,
+                    earlier.display(),
+                    later.display()
+                ),
+                strict,
+            )?;
+        }
+
+        match policy {
+            Collision::Error => bail!(
+                "Duplicate archive path {}: {} and {}",
+                name,
+                earlier.display(),
+                later.display()
+            ),
+            Collision::Skip => {
+                verbose!("Skipped duplicate {}: {}", name, later.display());
+                removed.insert(index);
+            }
+            Collision::Overwrite => {
+                verbose!("Overwrote duplicate {}: {}", name, earlier.display());
+                removed.insert(first);
+                kept.insert(name, index);
+            }
+            Collision::Rename => {
+                let target = renamed_target(&files[index].target, &taken);
+                let renamed = entry_name(&target);
+                verbose!(
+                    "Renamed duplicate {} to {}: {}",
+                    name,
+                    renamed,
+                    later.display()
+                );
+                taken.insert(renamed.clone());
+                kept.insert(renamed, index);
+                files[index].target = target;
+            }
+        }
+    }
+
+    let mut index = 0;
+    files.retain(|_| {
+        index += 1;
+        !removed.contains(&(index - 1))
+    });
+
+    Ok(())
+}
+
+/// Fails when an entry generated by the pack would replace a packed file.
+fn check_generated_collisions(files: &[File], generated: &[Generated]) -> Result<()> {
+    let packed = files
+        .iter()
+        .filter(|file| file.source.is_file())
+        .map(|file| (entry_name(&file.target), &file.source))
+        .collect::<HashMap<_, _>>();
+
+    for entry in generated {
+        let name = entry_name(&entry.target);
+        if let Some(source) = packed.get(&name) {
+            bail!(
+                "Duplicate archive path {}: {} and the entry generated by the pack",
+                name,
+                source.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+fn parse_transform(spec: &str) -> Result<Transform> {
+    let (pattern, command) = spec
+        .split_once('=')
+        .with_context(|| format!("Invalid transform, expected '<glob>=<command>': {}", spec))?;
+
+    let pattern = glob::Pattern::new(pattern.trim())
+        .with_context(|| format!("Invalid transform glob: {}", pattern))?;
+    let command = shlex::split(command)
+        .filter(|parts| !parts.is_empty())
+        .with_context(|| format!("Invalid transform command: {}", command))?;
+
+    Ok(Transform { pattern, command })
+}
+
+fn run_transform(command: &[String], input: &Path, output: &Path) -> Result<()> {
+    let args = command
+        .iter()
+        .map(|arg| {
+            arg.replace("{in}", &input.to_string_lossy())
+                .replace("{out}", &output.to_string_lossy())
+        })
+        .collect::<Vec<_>>();
+
+    let result = Command::new(&args[0])
+        .args(&args[1..])
+        .stdin(Stdio::null())
+        .output()
+        .with_context(|| format!("Failed to execute: {}", args[0]))?;
+
+    if !result.status.success() {
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        match stderr.trim() {
+            "" => bail!("{} exited with {}", args[0], result.status),
+            stderr => bail!("{} exited with {}: {}", args[0], result.status, stderr),
+        }
+    }
+
+    if !output.is_file() {
+        bail!("{} did not write its output", args[0]);
+    }
+
+    Ok(())
+}
+
+fn apply_transforms(specs: &[String], files: &mut [File], temp: &Path, strict: bool) -> Result<()> {
+    let transforms = specs
+        .iter()
+        .map(|spec| parse_transform(spec))
+        .collect::<Result<Vec<_>>>()?;
+
+    for (index, file) in files.iter_mut().enumerate() {
+        let name = entry_name(&file.target);
+        let Some(transform) = transforms.iter().find(|t| t.pattern.matches(&name)) else {
+            continue;
+        };
+        if !file.source.is_file() {
+            continue;
+        }
+
+        // Both sides keep the original file name so tools can rely on the extension.
+        let file_name = file.target.file_name().unwrap_or_default();
+        let input = temp.join(index.to_string()).join("in").join(file_name);
+        let output = temp.join(index.to_string()).join("out").join(file_name);
+
+        for dir in [input.parent(), output.parent()].into_iter().flatten() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create directory: {}", dir.display()))?;
+        }
+        fs::copy(&file.source, &input)
+            .with_context(|| format!("Failed to copy: {}", file.source.display()))?;
+
+        match run_transform(&transform.command, &input, &output) {
+            Ok(()) => {
+                info!("Transformed: {}", name);
+                file.source = output;
+            }
+            Err(e) => warn(&format!("Transform failed for {}: {:#}", name, e), strict)?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Rewrites matching JSON files with sorted keys and two-space indentation.
+/// The normalized copies are written to the temporary directory; sources are never modified.
+fn normalize_json(
+    patterns: &[String],
+    files: &mut [File],
+    temp: &Path,
+    strict: bool,
+) -> Result<()> {
+    let patterns = patterns
+        .iter()
+        .map(|p| glob::Pattern::new(p).with_context(|| format!("Invalid normalize glob: {}", p)))
+        .collect::<Result<Vec<_>>>()?;
+
+    for (index, file) in files.iter_mut().enumerate() {
+        let name = entry_name(&file.target);
+        if !file.source.is_file() || !patterns.iter().any(|p| p.matches(&name)) {
+            continue;
+        }
+
+        let content = fs::read_to_string(&file.source)
+            .with_context(|| format!("Failed to read: {}", file.source.display()))?;
+        let value = match serde_json::from_str::<serde_json::Value>(
+            content.strip_prefix('\u{feff}').unwrap_or(&content),
+        ) {
+            Ok(value) => value,
+            Err(e) => {
+                warn(&format!("Failed to normalize JSON {}: {}", name, e), strict)?;
+                continue;
+            }
+        };
+
+        let mut normalized = serde_json::to_vec_pretty(&value)?;
+        normalized.push(b'\n');
+
+        let output = temp
+            .join(index.to_string())
+            .join(file.target.file_name().unwrap_or_default());
+        if let Some(dir) = output.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create directory: {}", dir.display()))?;
+        }
+        fs::write(&output, normalized)
+            .with_context(|| format!("Failed to write: {}", output.display()))?;
+
+        verbose!("Normalized: {}", name);
+        file.source = output;
+    }
+
+    Ok(())
+}
+
+fn warn(message: &str, strict: bool) -> Result<()> {
+    if strict {
+        bail!("{}", message);
+    }
+    warning!("{}", message);
+    Ok(())
+}
+
+fn check_path_lengths(
+    files: &[File],
+    generated: &[Generated],
+    max: usize,
+    strict: bool,
+) -> Result<()> {
+    let mut paths = files
+        .iter()
+        .filter(|file| file.source.is_file())
+        .map(|file| entry_name(&file.target))
+        .chain(generated.iter().map(|entry| entry_name(&entry.target)))
+        .map(|path| (path.chars().count(), path))
+        .collect::<Vec<_>>();
+    paths.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+
+    if console::is_verbose() {
+        verbose!("Longest entry paths:");
+        for (length, path) in paths.iter().take(5) {
+            verbose!("  {:>4}  {}", length, path);
+        }
+    }
+
+    for (length, path) in paths.iter().take_while(|(length, _)| *length > max) {
+        warn(
+            &format!(
+                "Entry path is {} characters, exceeding {}: {}",
+                length, max, path
+            ),
+            strict,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Patterns of intermediate build files, paired with the rule reported when one matches.
+const INTERMEDIATE_FILES: &[(&str, &str)] = &[
+    ("**/obj/**", "inside an 'obj' directory"),
+    ("**/*.AssemblyInfo.cs", "generated assembly info"),
+    ("**/*.g.cs", "generated source"),
+    ("**/*.cache", "build cache"),
+];
+
+fn check_obj_leaks(files: &[File], strict: bool) -> Result<()> {
+    let rules = INTERMEDIATE_FILES
+        .iter()
+        .map(|(pattern, rule)| Ok((glob::Pattern::new(pattern)?, *rule)))
+        .collect::<Result<Vec<_>>>()?;
+    let options = glob::MatchOptions {
+        case_sensitive: false,
+        ..Default::default()
+    };
+
+    for file in files.iter().filter(|f| f.source.is_file()) {
+        let target = entry_name(&file.target);
+        if let Some((_, rule)) = rules
+            .iter()
+            .find(|(pattern, _)| pattern.matches_with(&target, options))
+        {
+            warn(
+                &format!("Intermediate build file ({}): {}", rule, target),
+                strict,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the SPDX identifier of the packed license, if one could be detected.
+fn check_license(files: &[File], strict: bool) -> Result<Option<&'static str>> {
+    let Some(file) = files
+        .iter()
+        .find(|file| file.source.is_file() && license::is_license_file(&file.target))
+    else {
+        warn("No license file included", strict)?;
+        return Ok(None);
+    };
+
+    let content = fs::read_to_string(&file.source)
+        .with_context(|| format!("Failed to read: {}", file.source.display()))?;
+
+    if content.trim().is_empty() {
+        warn(
+            &format!("License file is empty: {}", file.source.display()),
+            strict,
+        )?;
+        return Ok(None);
+    }
+
+    let license = license::detect(&content);
+    match license {
+        Some(license) => info!("Detected license: {}", license),
+        None => warning!("License could not be identified: {}", file.source.display()),
+    }
+
+    Ok(license)
+}
+
+fn verify_managed_assemblies(files: &[File], strict: bool) -> Result<()> {
+    let primary = primary_assembly(files);
+
+    for (index, file) in files.iter().enumerate() {
+        if !has_extension(&file.target, "dll") || !file.source.is_file() {
+            continue;
+        }
+
+        let data = fs::read(&file.source)
+            .with_context(|| format!("Failed to read: {}", file.source.display()))?;
+        let image = match pe::Image::parse(&data) {
+            Ok(image) => image,
+            Err(e) => {
+                let message = format!("Not a valid DLL: {}: {}", file.source.display(), e);
+                warn(&message, strict)?;
+                continue;
+            }
+        };
+
+        verbose!(
+            "{}: {}, {}",
+            entry_name(&file.target),
+            if image.is_managed() {
+                "managed"
+            } else {
+                "native"
+            },
+            image.architecture()?
+        );
+
+        if Some(index) == primary && !image.is_managed() {
+            warn(
+                &format!(
+                    "Mod assembly is not a managed .NET assembly: {}",
+                    file.source.display()
+                ),
+                strict,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+fn generate_dependencies_lock(files: &[File], strict: bool) -> Result<Generated> {
+    let mut assemblies = Vec::new();
+
+    for file in files.iter().filter(|f| f.source.is_file()) {
+        if !file
+            .target
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("dll"))
+        {
+            continue;
+        }
+
+        let data = fs::read(&file.source)
+            .with_context(|| format!("Failed to read: {}", file.source.display()))?;
+
+        match pe::Image::parse(&data).and_then(|image| image.assembly()) {
+            Ok(Some(assembly)) => assemblies.push(LockedAssembly {
+                path: entry_name(&file.target),
+                name: assembly.name,
+                version: assembly.version,
+            }),
+            Ok(None) => warn(
+                &format!("Not a managed assembly: {}", file.source.display()),
+                strict,
+            )?,
+            Err(e) => warn(
+                &format!(
+                    "Failed to read assembly version of {}: {}",
+                    file.source.display(),
+                    e
+                ),
+                strict,
+            )?,
+        }
+    }
+
+    assemblies.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let mut content = serde_json::to_vec_pretty(&DependencyLock { assemblies })?;
+    content.push(b'\n');
+
+    Ok(Generated {
+        target: PathBuf::from(DEPENDENCIES_LOCK),
+        content,
+    })
+}
+
+/// Build info recorded in the signed manifest by '--manifest-include-build-info'.
+fn manifest_build_info(build_commands: &[String]) -> Vec<(&'static str, String)> {
+    let mut info = build_commands
+        .iter()
+        .map(|command| ("build-command", command.clone()))
+        .collect::<Vec<_>>();
+    info.push(("packer-version", env!("CARGO_PKG_VERSION").to_string()));
+    info.push(("packed-at", time::timestamp()));
+    match git::hash() {
+        Some(commit) => info.push(("commit", commit)),
+        None => warning!("No git commit found for the manifest build info"),
+    }
+    info
+}
+
+fn generate_build_info() -> Result<Generated> {
+    let info = BuildInfo {
+        commit: git::hash(),
+        branch: git::branch(),
+        dirty: git::is_dirty(),
+        packed_at: time::timestamp(),
+        packer_version: env!("CARGO_PKG_VERSION"),
+    };
+    if info.commit.is_none() {
+        warning!("No git commit found for the build info");
+    }
+
+    let mut content = serde_json::to_vec_pretty(&info)?;
+    content.push(b'\n');
+
+    Ok(Generated {
+        target: PathBuf::from(BUILD_INFO),
+        content,
+    })
+}
+
+fn content_hash(path: &Path) -> Result<String> {
+    let content = fs::read(path).with_context(|| format!("Failed to read: {}", path.display()))?;
+    Ok(Sha256::digest(&content)
+        .iter()
+        .take(4)
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+fn hash_names(patterns: &[String], files: &mut [File]) -> Result<Generated> {
+    let patterns = patterns
+        .iter()
+        .map(|p| glob::Pattern::new(p).with_context(|| format!("Invalid content hash glob: {}", p)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut manifest = BTreeMap::new();
+    for file in files.iter_mut().filter(|f| f.source.is_file()) {
+        let original = entry_name(&file.target);
+        if !patterns.iter().any(|p| p.matches(&original)) {
+            continue;
+        }
+
+        let hash = content_hash(&file.source)?;
+        let stem = file
+            .target
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let name = match file.target.extension() {
+            Some(extension) => format!("{}.{}.{}", stem, hash, extension.to_string_lossy()),
+            None => format!("{}.{}", stem, hash),
+        };
+        file.target.set_file_name(name);
+
+        let hashed = entry_name(&file.target);
+        verbose!("Hashed: {} -> {}", original, hashed);
+        manifest.insert(original, hashed);
+    }
+
+    info!("Hashed {} entries", manifest.len());
+
+    let mut content = serde_json::to_vec_pretty(&manifest)?;
+    content.push(b'\n');
+
+    Ok(Generated {
+        target: PathBuf::from(ASSET_MANIFEST),
+        content,
+    })
+}
+
+/// Lists every packed file with its source modification time and size as TSV, sorted by target.
+fn generate_index(name: &str, files: &[File]) -> Result<Generated> {
+    let target = validate_segment(name)?;
+
+    let mut rows = files
+        .iter()
+        .filter(|file| file.source.is_file())
+        .map(|file| {
+            let metadata = fs::metadata(&file.source)
+                .with_context(|| format!("Failed to read metadata: {}", file.source.display()))?;
+            let modified = metadata
+                .modified()
+                .with_context(|| format!("Failed to read mtime: {}", file.source.display()))?;
+            Ok(format!(
+                "{}\t{}\t{}\n",
+                entry_name(&file.target),
+                time::format(modified),
+                metadata.len()
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    rows.sort();
+
+    let mut content = b"path\tmodified\tsize\n".to_vec();
+    content.extend(rows.concat().into_bytes());
+
+    Ok(Generated { target, content })
+}
+
+struct DiscardSink(u64);
+
+impl Write for DiscardSink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0 += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn compressed_size(reader: &mut impl std::io::Read) -> Result<u64> {
+    let mut encoder = DeflateEncoder::new(DiscardSink(0), Compression::default());
+    std::io::copy(reader, &mut encoder)?;
+    Ok(encoder.finish()?.0)
+}
+
+pub fn format_size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+    if size < 1024 {
+        return format!("{} B", size);
+    }
+
+    let mut value = size as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+#[derive(Default)]
+struct SizeStats {
+    count: usize,
+    uncompressed: u64,
+    compressed: u64,
+}
+
+/// Counts the file entries of an archive with their total size and compressed size.
+fn archive_totals(path: &Path) -> Result<(usize, u64, u64)> {
+    let file =
+        fs::File::open(path).with_context(|| format!("Failed to open: {}", path.display()))?;
+    let mut archive = zip::ZipArchive::new(file)
+        .with_context(|| format!("Failed to read archive: {}", path.display()))?;
+
+    let mut totals = (0, 0, 0);
+    for i in 0..archive.len() {
+        let entry = archive.by_index_raw(i)?;
+        if !entry.is_dir() {
+            totals.0 += 1;
+            totals.1 += entry.size();
+            totals.2 += entry.compressed_size();
+        }
+    }
+    Ok(totals)
+}
+
+/// Summaries of the packs of several mods, printed together as one JSON array when collected.
+static SUMMARIES: Mutex<Option<Vec<Summary>>> = Mutex::new(None);
+
+/// Collects the summaries of the following packs instead of printing each.
+pub fn collect_summaries() {
+    *SUMMARIES.lock().unwrap() = Some(Vec::new());
+}
+
+/// Prints the summaries collected since 'collect_summaries' as a JSON array.
+pub fn print_collected_summaries() -> Result<()> {
+    if let Some(summaries) = SUMMARIES.lock().unwrap().take() {
+        println!("{}", serde_json::to_string_pretty(&summaries)?);
+    }
+    Ok(())
+}
+
+fn print_summary(summary: Summary) -> Result<()> {
+    if let Some(summaries) = SUMMARIES.lock().unwrap().as_mut() {
+        summaries.push(summary);
+        return Ok(());
+    }
+    println!("{}", serde_json::to_string_pretty(&summary)?);
+    Ok(())
+}
+
+/// Prints the entries that would be packed as a tree, with sizes and source paths.
+fn print_tree(files: &[File], generated: &[Generated]) -> Result<()> {
+    let mut entries = BTreeMap::new();
+    for file in files.iter().filter(|f| f.source.is_file()) {
+        let size = fs::metadata(&file.source)
+            .with_context(|| format!("Failed to read metadata: {}", file.source.display()))?
+            .len();
+        let source = file.source.display().to_string();
+        entries.insert(entry_name(&file.target), (size, source));
+    }
+    for entry in generated {
+        let size = entry.content.len() as u64;
+        entries.insert(entry_name(&entry.target), (size, "(generated)".to_string()));
+    }
+
+    let mut lines = Vec::new();
+    let mut previous: Vec<&str> = Vec::new();
+    for (name, (size, source)) in &entries {
+        let parts = name.split('/').collect::<Vec<_>>();
+        let (file_name, dirs) = parts.split_last().unwrap_or((&"", &[]));
+
+        // Print the directories not shared with the previous entry.
+        let shared = dirs
+            .iter()
+            .zip(&previous)
+            .take_while(|(a, b)| a == b)
+            .count();
+        for (depth, dir) in dirs.iter().enumerate().skip(shared) {
+            lines.push((format!("{}{}/", "  ".repeat(depth), dir), None));
+        }
+        previous = dirs.to_vec();
+
+        let label = format!("{}{}", "  ".repeat(dirs.len()), file_name);
+        lines.push((label, Some((*size, source.as_str()))));
+    }
+
+    let width = lines
+        .iter()
+        .map(|(label, _)| label.len())
+        .max()
+        .unwrap_or(0);
+    let total: u64 = entries.values().map(|(size, _)| size).sum();
+
+    info!();
+    for (label, file) in &lines {
+        match file {
+            Some((size, source)) => info!(
+                "{:<width$}{:>12}  <- {}",
+                label,
+                format_size(*size),
+                source,
+                width = width
+            ),
+            None => info!("{}", label),
+        }
+    }
+    info!();
+    info!("{} entries, {}", entries.len(), format_size(total));
+
+    Ok(())
+}
+
+fn print_stats(files: &[File], generated: &[Generated]) -> Result<()> {
+    let mut total = SizeStats::default();
+    let mut extensions: BTreeMap<String, SizeStats> = BTreeMap::new();
+    let mut archive = 22;
+
+    let mut record = |target: &Path, uncompressed: u64, compressed: u64| {
+        let extension = target
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_else(|| "(none)".to_string());
+
+        for stats in [&mut total, extensions.entry(extension).or_default()] {
+            stats.count += 1;
+            stats.uncompressed += uncompressed;
+            stats.compressed += compressed;
+        }
+
+        let name = target.to_string_lossy().len() as u64;
+        archive += 30 + 46 + name * 2 + compressed;
+    };
+
+    for file in files.iter().filter(|f| f.source.is_file()) {
+        let mut content = fs::File::open(&file.source)
+            .with_context(|| format!("Failed to open: {}", file.source.display()))?;
+        let uncompressed = content.metadata()?.len();
+        record(&file.target, uncompressed, compressed_size(&mut content)?);
+    }
+
+    for entry in generated {
+        let compressed = compressed_size(&mut entry.content.as_slice())?;
+        record(&entry.target, entry.content.len() as u64, compressed);
+    }
+
+    info!(
+        "{:<12}{:>8}{:>14}{:>14}",
+        "Extension", "Files", "Size", "Compressed"
+    );
+    for (extension, stats) in &extensions {
+        info!(
+            "{:<12}{:>8}{:>14}{:>14}",
+            extension,
+            stats.count,
+            format_size(stats.uncompressed),
+            format_size(stats.compressed)
+        );
+    }
+    info!(
+        "{:<12}{:>8}{:>14}{:>14}",
+        "Total",
+        total.count,
+        format_size(total.uncompressed),
+        format_size(total.compressed)
+    );
+    info!("Projected archive size: {}\n", format_size(archive));
+
+    Ok(())
+}
+
+fn is_transient(error: &std::io::Error) -> bool {
+    matches!(
+        error.kind(),
+        ErrorKind::Interrupted
+            | ErrorKind::TimedOut
+            | ErrorKind::WouldBlock
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::NotConnected
+            | ErrorKind::StaleNetworkFileHandle
+            | ErrorKind::ResourceBusy
+    )
+}
+
+/// Writes one file entry, retrying transient read errors.
+/// A read error that persists is returned as the inner error, with any partial entry removed.
+fn write_entry<W: Write + Seek>(
+    zip: &mut zip::ZipWriter<W>,
+    file: &File,
+    options: &EntryOptions,
+    retries: u32,
+) -> Result<std::io::Result<()>> {
+    let path = entry_name(&file.target);
+    let mut attempt = 0;
+
+    loop {
+        let result = match fs::File::open(&file.source) {
+            Ok(mut content) => {
+                let large = content
+                    .metadata()
+                    .is_ok_and(|metadata| metadata.len() >= zip::ZIP64_BYTES_THR);
+                if large {
+                    warning!("Writing ZIP64 entry for file over 4 GiB: {}", path);
+                }
+                let options = options.for_entry(&file.target).large_file(large);
+                zip.start_file(path.as_str(), options)?;
+                let copied = std::io::copy(&mut content, &mut *zip).map(|_| ());
+                if copied.is_err() {
+                    // Drop the partially written entry so nothing truncated is packed.
+                    zip.abort_file()?;
+                }
+                copied
+            }
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Err(e) if attempt < retries && is_transient(&e) => {
+                attempt += 1;
+                verbose!(
+                    "Retrying {} ({}/{}): {}",
+                    file.source.display(),
+                    attempt,
+                    retries,
+                    e
+                );
+                std::thread::sleep(Duration::from_millis(100 << (attempt - 1).min(5)));
+            }
+            result => return Ok(result),
+        }
+    }
+}
+
+fn compression_method(method: Method) -> Result<CompressionMethod> {
+    Ok(match method {
+        Method::Stored => CompressionMethod::Stored,
+        Method::Deflate => CompressionMethod::Deflated,
+        #[cfg(feature = "bzip2")]
+        Method::Bzip2 => {
+            warning!("NeoModLoader may not be able to read bzip2 entries");
+            CompressionMethod::Bzip2
+        }
+        #[cfg(not(feature = "bzip2"))]
+        Method::Bzip2 => bail!("Built without bzip2 support, enable the 'bzip2' feature"),
+        #[cfg(feature = "zstd")]
+        Method::Zstd => {
+            warning!("NeoModLoader may not be able to read zstd entries");
+            CompressionMethod::Zstd
+        }
+        #[cfg(not(feature = "zstd"))]
+        Method::Zstd => bail!("Built without zstd support, enable the 'zstd' feature"),
+    })
+}
+
+/// Extensions of already-compressed formats, stored by default instead of compressed again.
+const STORED_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "ogg", "zip"];
+
+/// Zip options of packed entries, with the compression method chosen by extension.
+struct EntryOptions {
+    base: SimpleFileOptions,
+    method: CompressionMethod,
+    level: Option<i64>,
+    rules: HashMap<String, CompressionMethod>,
+}
+
+impl EntryOptions {
+    fn new(method: Method, level: Option<i64>, rules: &[(String, Method)]) -> Result<Self> {
+        if level.is_some() && matches!(method, Method::Stored) {
+            warning!("'--compression-level' only applies to entries matched by a compressing rule");
+        }
+
+        let mut options = EntryOptions {
+            base: SimpleFileOptions::default(),
+            method: compression_method(method)?,
+            level,
+            rules: STORED_EXTENSIONS
+                .iter()
+                .map(|extension| (extension.to_string(), CompressionMethod::Stored))
+                .collect(),
+        };
+        for (extension, method) in rules {
+            options
+                .rules
+                .insert(extension.clone(), compression_method(*method)?);
+        }
+        Ok(options)
+    }
+
+    fn for_entry(&self, target: &Path) -> SimpleFileOptions {
+        let method = target
+            .extension()
+            .and_then(|e| self.rules.get(&e.to_string_lossy().to_lowercase()))
+            .copied()
+            .unwrap_or(self.method);
+
+        let options = self.base.compression_method(method);
+        if method == CompressionMethod::Stored {
+            options
+        } else {
+            options.compression_level(self.level)
+        }
+    }
+}
+
+/// A file left out of the package under '--keep-going', with the reason.
+type Skipped = (PathBuf, String);
+
+fn skip_or_fail(file: &File, error: std::io::Error, keep_going: bool) -> Result<Skipped> {
+    if !keep_going {
+        return Err(error).with_context(|| format!("Failed to read: {}", file.source.display()));
+    }
+
+    error!("Skipped {}: {}", file.source.display(), error);
+    Ok((file.source.clone(), error.to_string()))
+}
+
+/// How the build command runs, shared by every configuration.
+struct BuildProcess {
+    env: Vec<(String, String)>,
+    timeout: Option<Duration>,
+    /// File receiving the stdout and stderr of the build.
+    log: Option<Arc<Mutex<fs::File>>>,
+}
+
+/// How archives are written, independent of the options of their entries.
+struct WriteOptions {
+    /// Number of threads compressing entries.
+    jobs: usize,
+    retries: u32,
+    keep_going: bool,
+    /// Whether an existing archive with different contents may be replaced.
+    force: bool,
+    cache: Option<EntryCache>,
+}
+
+/// Compressed entries of the last pack, stored as single-entry archives named by their key.
+struct EntryCache {
+    dir: PathBuf,
+    /// Keys of the entries used by this pack, the others are removed afterwards.
+    used: Mutex<HashSet<String>>,
+}
+
+impl EntryCache {
+    /// Hashes the entry name, its options and the file content, or nothing if the file cannot be read.
+    fn key(&self, file: &File, options: &EntryOptions) -> Option<String> {
+        let mut content = fs::File::open(&file.source).ok()?;
+        let mut hasher = Sha256::new();
+        hasher.update(entry_name(&file.target).as_bytes());
+        hasher.update(format!("{:?}", options.for_entry(&file.target)).as_bytes());
+        std::io::copy(&mut content, &mut hasher).ok()?;
+
+        let key = signing::to_hex(&hasher.finalize());
+        self.used.lock().unwrap().insert(key.clone());
+        Some(key)
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.zip", key))
+    }
+
+    /// Whether a cached entry exists and is a readable archive, unlike one truncated by a crash.
+    fn is_readable(path: &Path) -> bool {
+        let Ok(content) = fs::File::open(path) else {
+            return false;
+        };
+        let readable =
+            zip::ZipArchive::new(content).is_ok_and(|mut archive| archive.by_index_raw(0).is_ok());
+        if !readable {
+            verbose!("Ignored unreadable cached entry: {}", path.display());
+        }
+        readable
+    }
+
+    /// Compresses a file into the cache through a temporary file renamed into place,
+    /// so an interrupted write is never read back.
+    fn store(
+        path: &Path,
+        file: &File,
+        options: &EntryOptions,
+        retries: u32,
+    ) -> Result<std::io::Result<()>> {
+        let pending = signing::with_suffix(path, &format!(".{}.tmp", std::process::id()));
+        let content = fs::File::create(&pending)
+            .with_context(|| format!("Failed to create file: {}", pending.display()))?;
+
+        let mut zip = zip::ZipWriter::new(content);
+        let result = write_entry(&mut zip, file, options, retries).and_then(|written| {
+            if written.is_ok() {
+                zip.finish()?;
+                fs::rename(&pending, path)
+                    .with_context(|| format!("Failed to write: {}", path.display()))?;
+            }
+            Ok(written)
+        });
+        if !matches!(result, Ok(Ok(()))) {
+            let _ = fs::remove_file(&pending);
+        }
+        result
+    }
+
+    /// Returns the key of a cache entry file, named '<key>.zip' with a SHA-256 hex key.
+    fn key_of(path: &Path) -> Option<&str> {
+        let key = path.file_name()?.to_str()?.strip_suffix(".zip")?;
+        (key.len() == 64 && key.bytes().all(|b| b.is_ascii_hexdigit())).then_some(key)
+    }
+
+    /// Removes the entries not used by this pack.
+    /// Other files in the directory are never touched, as it may be shared.
+    fn prune(&self) -> Result<()> {
+        let used = self.used.lock().unwrap();
+        let entries = fs::read_dir(&self.dir)
+            .with_context(|| format!("Failed to read directory: {}", self.dir.display()))?;
+        for entry in entries {
+            let path = entry?.path();
+            let stale = Self::key_of(&path).is_some_and(|key| !used.contains(key));
+            if stale && path.is_file() {
+                fs::remove_file(&path)
+                    .with_context(|| format!("Failed to remove: {}", path.display()))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Archive being written, removed if the process is interrupted.
+static PENDING_OUTPUT: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Running build command, killed if the process is interrupted or the build times out.
+static BUILD_CHILD: Mutex<Option<std::process::Child>> = Mutex::new(None);
+
+/// Kills the running build command and waits for it to exit.
+fn kill_build() {
+    if let Some(mut child) = BUILD_CHILD.lock().unwrap().take() {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
+
+/// On Ctrl+C, kills the build command and removes the archive being written before exiting.
+fn handle_interrupt() {
+    static HANDLER: Once = Once::new();
+    HANDLER.call_once(|| {
+        let _ = ctrlc::set_handler(|| {
+            kill_build();
+            if let Some(path) = PENDING_OUTPUT.lock().unwrap().take() {
+                let _ = fs::remove_file(path);
+            }
+            std::process::exit(130);
+        });
+    });
+}
+
+/// Writes an archive to '<path>.tmp' and renames it into place once complete,
+/// so a failed or interrupted pack never leaves a truncated archive at the output path.
+fn zip(
+    path: &Path,
+    files: &[File],
+    generated: &[Generated],
+    options: &EntryOptions,
+    write: &WriteOptions,
+) -> Result<Vec<Skipped>> {
+    let pending = signing::with_suffix(path, ".tmp");
+    handle_interrupt();
+    *PENDING_OUTPUT.lock().unwrap() = Some(pending.clone());
+
+    let result = write_archive(&pending, files, generated, options, write)
+        .and_then(|skipped| replace_output(&pending, path, write.force).map(|_| skipped));
+    if result.is_err() {
+        let _ = fs::remove_file(&pending);
+    }
+
+    *PENDING_OUTPUT.lock().unwrap() = None;
+    result
+}
+
+/// Moves a completed archive to the output path.
+/// An existing archive is only replaced by identical contents unless forced.
+fn replace_output(pending: &Path, path: &Path, force: bool) -> Result<()> {
+    if path.exists() && !force {
+        if !same_content(pending, path)? {
+            bail!(
+                "Package already exists with different contents, pass '--force' to overwrite: {}",
+                path.display()
+            );
+        }
+        verbose!("Package is unchanged: {}", path.display());
+        return fs::remove_file(pending)
+            .with_context(|| format!("Failed to remove: {}", pending.display()));
+    }
+
+    fs::rename(pending, path).with_context(|| format!("Failed to write: {}", path.display()))
+}
+
+fn write_archive(
+    pending: &Path,
+    files: &[File],
+    generated: &[Generated],
+    options: &EntryOptions,
+    write: &WriteOptions,
+) -> Result<Vec<Skipped>> {
+    let file = fs::File::create(pending)
+        .with_context(|| format!("Failed to create file: {}", pending.display()))?;
+    let mut zip = zip::ZipWriter::new(file);
+
+    let files = files
+        .iter()
+        .filter(|f| f.source.exists() && !f.source.is_dir())
+        .collect::<Vec<_>>();
+
+    let mut skipped = Vec::new();
+    let mut record = |file: &File, result: std::io::Result<()>| -> Result<()> {
+        match result {
+            Ok(()) => trace!(
+                "Wrote entry: {} <- {}",
+                entry_name(&file.target),
+                file.source.display()
+            ),
+            Err(e) => skipped.push(skip_or_fail(file, e, write.keep_going)?),
+        }
+        console::advance();
+        Ok(())
+    };
+
+    if write.cache.is_none() && (write.jobs <= 1 || files.len() <= 1) {
+        for file in &files {
+            record(file, write_entry(&mut zip, file, options, write.retries)?)?;
+        }
+    } else {
+        compress_in_parallel(&files, options, write, |index, compressed| {
+            let result = match compressed {
+                Ok(Compressed::Bytes(bytes)) => {
+                    append_compressed(&mut zip, std::io::Cursor::new(bytes))?;
+                    Ok(())
+                }
+                Ok(Compressed::Cached(path)) => {
+                    let content = fs::File::open(&path)
+                        .with_context(|| format!("Failed to open: {}", path.display()))?;
+                    append_compressed(&mut zip, content).with_context(|| {
+                        format!("Failed to read cached entry: {}", path.display())
+                    })?;
+                    Ok(())
+                }
+                Ok(Compressed::Stream) => {
+                    write_entry(&mut zip, files[index], options, write.retries)?
+                }
+                Err(e) => Err(e),
+            };
+            record(files[index], result)
+        })?;
+    }
+
+    for entry in generated {
+        let path = entry_name(&entry.target);
+        trace!("Wrote entry: {} <- (generated)", path);
+        zip.start_file(path, options.for_entry(&entry.target))?;
+        zip.write_all(&entry.content)?;
+        console::advance();
+    }
+
+    zip.finish()?;
+    Ok(skipped)
+}
+
+/// Files larger than this are compressed by the writer thread straight into the archive,
+/// rather than buffered in memory by a worker.
+const STREAM_SIZE: u64 = 16 * 1024 * 1024;
+
+/// An entry prepared by a worker for the writer.
+enum Compressed {
+    /// Single-entry archive compressed in memory.
+    Bytes(Vec<u8>),
+    /// Single-entry archive in the cache.
+    Cached(PathBuf),
+    /// File too large to buffer, left for the writer to compress.
+    Stream,
+}
+
+/// Compresses a file into a single-entry archive in memory or in the cache, reusing a cached one.
+fn compress_entry(
+    file: &File,
+    options: &EntryOptions,
+    write: &WriteOptions,
+) -> Result<std::io::Result<Compressed>> {
+    if let Some(cache) = &write.cache
+        && let Some(key) = cache.key(file, options)
+    {
+        let path = cache.path(&key);
+        if EntryCache::is_readable(&path) {
+            trace!("Reused compressed entry: {}", entry_name(&file.target));
+        } else if let Err(e) = EntryCache::store(&path, file, options, write.retries)? {
+            return Ok(Err(e));
+        }
+        return Ok(Ok(Compressed::Cached(path)));
+    }
+
+    let large = fs::metadata(&file.source).is_ok_and(|metadata| metadata.len() > STREAM_SIZE);
+    if large {
+        return Ok(Ok(Compressed::Stream));
+    }
+
+    let mut buffer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    Ok(
+        match write_entry(&mut buffer, file, options, write.retries)? {
+            Ok(()) => Ok(Compressed::Bytes(buffer.finish()?.into_inner())),
+            Err(e) => Err(e),
+        },
+    )
+}
+
+/// Appends the entry of an archive made by 'compress_entry' without recompressing it.
+fn append_compressed(
+    zip: &mut zip::ZipWriter<fs::File>,
+    source: impl std::io::Read + Seek,
+) -> Result<()> {
+    let mut archive = zip::ZipArchive::new(source)?;
+    zip.raw_copy_file(archive.by_index_raw(0)?)?;
+    Ok(())
+}
+
+/// Compresses files on worker threads, handing each result to 'on_entry' in the original order.
+/// Workers stay at most two entries per thread ahead of the writer, bounding the entries held in memory.
+fn compress_in_parallel(
+    files: &[&File],
+    options: &EntryOptions,
+    write: &WriteOptions,
+    mut on_entry: impl FnMut(usize, std::io::Result<Compressed>) -> Result<()>,
+) -> Result<()> {
+    let next = AtomicUsize::new(0);
+    let window = write.jobs * 2;
+    // Entries written so far, or nothing once the writer stopped.
+    let written = (Mutex::new(Some(0)), Condvar::new());
+    std::thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel();
+        for _ in 0..write.jobs.min(files.len()) {
+            let sender = sender.clone();
+            let (next, written) = (&next, &written);
+            scope.spawn(move || {
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    if index >= files.len() {
+                        break;
+                    }
+                    let mut count = written.0.lock().unwrap();
+                    while let Some(done) = *count
+                        && index >= done + window
+                    {
+                        count = written.1.wait(count).unwrap();
+                    }
+                    if count.is_none() {
+                        break;
+                    }
+                    drop(count);
+
+                    let result = compress_entry(files[index], options, write);
+                    // The writer stopped after an error, the remaining entries are not needed.
+                    if sender.send((index, result)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        // Entries finish out of order and wait until those before them are written.
+        let mut finished = BTreeMap::new();
+        let mut expected = 0;
+        let result = receiver.into_iter().try_for_each(|(index, result)| {
+            finished.insert(index, result);
+            while let Some(result) = finished.remove(&expected) {
+                on_entry(expected, result?)?;
+                expected += 1;
+                *written.0.lock().unwrap() = Some(expected);
+                written.1.notify_all();
+            }
+            Ok(())
+        });
+
+        // Wakes the waiting workers, which stop if the writer failed.
+        *written.0.lock().unwrap() = None;
+        written.1.notify_all();
+        result
+    })
+}
+
+fn volume_path(output: &Path, number: usize) -> PathBuf {
+    let mut path = output.as_os_str().to_owned();
+    path.push(format!(".{:03}", number));
+    PathBuf::from(path)
+}
+
+/// Discards an archive written to it, keeping its length, and supports the seeks of a zip writer.
+#[derive(Default)]
+struct MeasureSink {
+    position: u64,
+    len: u64,
+}
+
+impl Write for MeasureSink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.position += buf.len() as u64;
+        self.len = self.len.max(self.position);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for MeasureSink {
+    fn seek(&mut self, position: std::io::SeekFrom) -> std::io::Result<u64> {
+        let position = match position {
+            std::io::SeekFrom::Start(offset) => Some(offset),
+            std::io::SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            std::io::SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        self.position = position
+            .ok_or_else(|| std::io::Error::new(ErrorKind::InvalidInput, "Seek before the start"))?;
+        Ok(self.position)
+    }
+}
+
+/// Measures the space an entry takes in an archive by writing it with its own compression
+/// to a sink: the compressed content plus the local and central directory headers.
+fn entry_size(
+    target: &Path,
+    size: u64,
+    options: &EntryOptions,
+    reader: &mut impl std::io::Read,
+) -> Result<u64> {
+    // The end of central directory record is counted once per volume.
+    const END_RECORD: u64 = 22;
+
+    let mut zip = zip::ZipWriter::new(MeasureSink::default());
+    let large = size >= zip::ZIP64_BYTES_THR;
+    zip.start_file(
+        entry_name(target),
+        options.for_entry(target).large_file(large),
+    )?;
+    std::io::copy(reader, &mut zip)?;
+    Ok(zip.finish()?.len - END_RECORD)
+}
+
+type Volume = (Vec<File>, Vec<Generated>, u64);
+
+/// Returns the index of the volume receiving an entry, opening a new one when the last is full.
+fn place_in_volume(
+    volumes: &mut Vec<Volume>,
+    size: u64,
+    target: &Path,
+    limit: u64,
+) -> Result<usize> {
+    // The end of central directory record closes every volume.
+    const END_RECORD: u64 = 22;
+
+    if size + END_RECORD > limit {
+        bail!(
+            "Entry alone exceeds the split size of {}: {}",
+            format_size(limit),
+            entry_name(target)
+        );
+    }
+
+    match volumes.last_mut() {
+        Some((_, _, used)) if *used + size <= limit => *used += size,
+        _ => volumes.push((Vec::new(), Vec::new(), END_RECORD + size)),
+    }
+    Ok(volumes.len() - 1)
+}
+
+/// Writes the package as independent archives of whole entries, each below the limit.
+fn write_volumes(
+    output: &Path,
+    files: &[File],
+    generated: &[Generated],
+    limit: u64,
+    options: &EntryOptions,
+    write: &WriteOptions,
+) -> Result<(Vec<PathBuf>, Vec<Skipped>)> {
+    let mut volumes = Vec::new();
+    let mut skipped = Vec::new();
+    for file in files.iter().filter(|f| f.source.is_file()) {
+        let mut content = match fs::File::open(&file.source) {
+            Ok(content) => content,
+            Err(e) => {
+                skipped.push(skip_or_fail(file, e, write.keep_going)?);
+                continue;
+            }
+        };
+        let len = content.metadata()?.len();
+        let size = entry_size(&file.target, len, options, &mut content)?;
+        let index = place_in_volume(&mut volumes, size, &file.target, limit)?;
+        volumes[index].0.push(file.clone());
+    }
+    for entry in generated {
+        let len = entry.content.len() as u64;
+        let size = entry_size(&entry.target, len, options, &mut entry.content.as_slice())?;
+        let index = place_in_volume(&mut volumes, size, &entry.target, limit)?;
+        volumes[index].1.push(entry.clone());
+    }
+
+    let mut paths = Vec::new();
+    for (index, (files, generated, _)) in volumes.iter().enumerate() {
+        let path = volume_path(output, index + 1);
+        skipped.extend(zip(&path, files, generated, options, write)?);
+
+        let size = fs::metadata(&path)
+            .with_context(|| format!("Failed to read metadata: {}", path.display()))?
+            .len();
+        if size > limit {
+            bail!(
+                "Volume is {}, exceeding the split size of {}: {}",
+                format_size(size),
+                format_size(limit),
+                path.display()
+            );
+        }
+        paths.push(path);
+    }
+
+    // Volumes left over from an earlier, larger package would be extracted alongside.
+    let mut stale = volume_path(output, paths.len() + 1);
+    while stale.is_file() {
+        fs::remove_file(&stale)
+            .with_context(|| format!("Failed to remove: {}", stale.display()))?;
+        stale = volume_path(output, paths.len() + 1);
+    }
+
+    Ok((paths, skipped))
+}
+
+const LOCK_FILE: &str = ".nmlpack.lock";
+
+/// Takes an advisory lock on the output directory, failing fast when another pack holds it.
+fn lock_output_dir(output: &Path) -> Result<fs::File> {
+    let dir = match output.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let path = dir.join(LOCK_FILE);
+
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open: {}", path.display()))?;
+
+    match file.try_lock() {
+        Ok(()) => Ok(file),
+        Err(fs::TryLockError::WouldBlock) => bail!(
+            "Another pack is writing to the output directory, lock held on: {}",
+            path.display()
+        ),
+        Err(fs::TryLockError::Error(e)) => {
+            Err(e).with_context(|| format!("Failed to lock: {}", path.display()))
+        }
+    }
+}
+
+fn ensure_gitignore(output: &Path) -> Result<()> {
+    let root = std::env::current_dir().context("Failed to get current directory")?;
+    let parent = absolute(output)?
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+
+    let Ok(relative) = parent.strip_prefix(&root) else {
+        warning!(
+            "Output directory is outside the project, skipped '.gitignore': {}",
+            parent.display()
+        );
+        return Ok(());
+    };
+    if relative.as_os_str().is_empty() {
+        warning!("Output directory is the project root, skipped '.gitignore'");
+        return Ok(());
+    }
+
+    let pattern = format!("{}/", entry_name(relative));
+    let path = root.join(".gitignore");
+    let content = if path.exists() {
+        fs::read_to_string(&path).with_context(|| format!("Failed to read: {}", path.display()))?
+    } else {
+        String::new()
+    };
+
+    let trimmed = pattern.trim_end_matches('/');
+    let present = content.lines().map(str::trim).any(|line| {
+        let line = line.trim_start_matches('/');
+        line == pattern || line == trimmed
+    });
+    if present {
+        info!("'.gitignore' already ignores: {}", pattern);
+        return Ok(());
+    }
+
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open: {}", path.display()))?;
+    let separator = if content.is_empty() || content.ends_with('\n') {
+        ""
+    } else {
+        "\n"
+    };
+    writeln!(file, "{}{}", separator, pattern)
+        .with_context(|| format!("Failed to write: {}", path.display()))?;
+
+    info!("Added to '.gitignore': {}", pattern);
+    Ok(())
+}
+
+/// Extra field holding the sizes of entries over 4 GiB, required rather than platform-specific.
+const ZIP64_EXTRA_FIELD: u16 = 0x0001;
+
+/// Returns the ID of the first extra field other than ZIP64, or 0xffff for malformed data.
+fn unexpected_extra_field(mut data: &[u8]) -> Option<u16> {
+    while !data.is_empty() {
+        let [id_low, id_high, len_low, len_high, rest @ ..] = data else {
+            return Some(0xffff);
+        };
+        let id = u16::from_le_bytes([*id_low, *id_high]);
+        if id != ZIP64_EXTRA_FIELD {
+            return Some(id);
+        }
+        let len = u16::from_le_bytes([*len_low, *len_high]) as usize;
+        let Some(next) = rest.get(len..) else {
+            return Some(0xffff);
+        };
+        data = next;
+    }
+    None
+}
+
+fn verify_no_extra_fields(path: &Path) -> Result<()> {
+    let file =
+        fs::File::open(path).with_context(|| format!("Failed to open: {}", path.display()))?;
+    let mut archive = zip::ZipArchive::new(file)
+        .with_context(|| format!("Failed to read archive: {}", path.display()))?;
+
+    for i in 0..archive.len() {
+        let entry = archive.by_index_raw(i)?;
+        if let Some(id) = entry.extra_data().and_then(unexpected_extra_field) {
+            bail!(
+                "Unexpected extra field 0x{:04x} in entry: {}",
+                id,
+                entry.name()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn preview_dir() -> PathBuf {
+    std::env::temp_dir().join("nmlpack-previews")
+}
+
+pub fn clean_previews() -> Result<()> {
+    let dir = preview_dir();
+    if !dir.exists() {
+        return Ok(());
+    }
+
+    fs::remove_dir_all(&dir).with_context(|| format!("Failed to remove: {}", dir.display()))?;
+    info!("Removed previews: {}", dir.display());
+    Ok(())
+}
+
+/// Files written next to a package, deleted along with it.
+const SIDECAR_SUFFIXES: &[&str] = &[".sha256", ".sig", ".manifest", ".manifest.sig"];
+
+/// Group of a package: its file stem without the version of its 'mod.json',
+/// e.g. 'MyMod' of 'MyMod-1.2.0' or 'pre-MyMod-beta' of 'pre-MyMod-1.2.0-beta'.
+/// Stems not containing the mod's name and version belong to no group.
+fn package_group(stem: &str, metadata: &Mod) -> Option<String> {
+    let name = sanitize_file_name(&metadata.name);
+    let versioned = sanitize_file_name(&format!("{}-{}", metadata.name, metadata.version));
+    stem.contains(&versioned)
+        .then(|| stem.replacen(&versioned, &name, 1))
+}
+
+/// Reads the 'mod.json' at the root of a package, or of a folder it was extracted into.
+fn read_packaged_mod(path: &Path) -> Option<Mod> {
+    let content = if path.is_dir() {
+        fs::read_to_string(path.join("mod.json")).ok()?
+    } else {
+        let mut archive = zip::ZipArchive::new(fs::File::open(path).ok()?).ok()?;
+        std::io::read_to_string(archive.by_name("mod.json").ok()?).ok()?
+    };
+    serde_json::from_str(&content).ok()
+}
+
+/// Group of a package or of a folder it was extracted into, if it is named like a package.
+pub(crate) fn package_group_of(path: &Path) -> Option<String> {
+    let name = if path.is_dir() {
+        path.file_name()?
+    } else {
+        path.file_stem()?
+    };
+    package_group(&name.to_string_lossy(), &read_packaged_mod(path)?)
+}
+
+/// Deletes all but the 'keep' most recently modified packages of each mod in the directory,
+/// or of only the given mod. Returns the number of deleted packages.
+pub fn prune_packages(dir: &Path, keep: usize, only: Option<&str>) -> Result<usize> {
+    let entries = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read directory: {}", dir.display()))?;
+
+    let mut groups: BTreeMap<String, Vec<(std::time::SystemTime, PathBuf)>> = BTreeMap::new();
+    for entry in entries {
+        let path = entry?.path();
+        if !path.is_file() || path.extension().is_none_or(|ext| ext != "zip") {
+            continue;
+        }
+
+        let Some(group) = package_group_of(&path) else {
+            continue;
+        };
+        if only.is_some_and(|only| only != group) {
+            continue;
+        }
+
+        let modified = fs::metadata(&path)
+            .and_then(|m| m.modified())
+            .with_context(|| format!("Failed to read metadata: {}", path.display()))?;
+        groups.entry(group).or_default().push((modified, path));
+    }
+
+    let mut removed = 0;
+    for packages in groups.values_mut() {
+        packages.sort_by(|a, b| b.cmp(a));
+        for (_, path) in packages.iter().skip(keep) {
+            fs::remove_file(path)
+                .with_context(|| format!("Failed to remove: {}", path.display()))?;
+            for suffix in SIDECAR_SUFFIXES {
+                let sidecar = signing::with_suffix(path, suffix);
+                if sidecar.is_file() {
+                    fs::remove_file(&sidecar)
+                        .with_context(|| format!("Failed to remove: {}", sidecar.display()))?;
+                }
+            }
+            info!("Removed old package: {}", path.display());
+            removed += 1;
+        }
+    }
+
+    Ok(removed)
+}
+
+fn print_packed_message(output: &PathBuf) -> Result<()> {
+    let output = absolute(output)
+        .context("Failed to absolute path")?
+        .display()
+        .to_string();
+
+    info!(
+        "Packed mod at: \x1b]8;;file://{}\x1b\\{}\x1b]8;;\x1b\\",
+        output.replace('\\', "/"),
+        output
+    );
+
+    Ok(())
+}
+
+fn open_in_file_manager(path: &Path) -> Result<()> {
+    if std::env::var_os("CI").is_some() || !std::io::stdout().is_terminal() {
+        warning!("Skipped opening the file manager in a non-interactive environment");
+        return Ok(());
+    }
+
+    let path = absolute(path).context("Failed to absolute path")?;
+
+    let mut command;
+    if cfg!(windows) {
+        command = Command::new("explorer");
+        command.arg(format!("/select,{}", path.display()));
+    } else if cfg!(target_os = "macos") {
+        command = Command::new("open");
+        command.arg("-R").arg(&path);
+    } else {
+        command = Command::new("xdg-open");
+        command.arg(path.parent().unwrap_or(&path));
+    }
+
+    let spawned = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+
+    if let Err(e) = spawned {
+        warning!("Failed to open the file manager: {}", e);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates the given files, with their path as content, below a temporary directory.
+    fn fixture(name: &str, paths: &[&str]) -> TempDir {
+        let dir = TempDir::new(&format!("test-{}", name)).unwrap();
+        for path in paths {
+            let path = dir.0.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, path.to_string_lossy().as_bytes()).unwrap();
+        }
+        dir
+    }
+
+    fn targets(files: &[File]) -> Vec<String> {
+        let mut targets = files
+            .iter()
+            .map(|file| entry_name(&file.target))
+            .collect::<Vec<_>>();
+        targets.sort();
+        targets
+    }
+
+    #[test]
+    fn sources_skip_project_files_and_build_dirs() {
+        let dir = fixture(
+            "sources",
+            &[
+                "src/Main.cs",
+                "src/Mod.csproj",
+                "src/Mod.csproj.user",
+                "src/Util/Helper.cs",
+                "src/obj/Debug/Mod.AssemblyInfo.cs",
+                "src/bin/Generated.cs",
+            ],
+        );
+        let source = dir.0.join("src").to_string_lossy().into_owned();
+
+        let mut files = Vec::new();
+        collect_sources(std::slice::from_ref(&source), false, &mut files).unwrap();
+        assert_eq!(targets(&files), ["src/Main.cs", "src/Util/Helper.cs"]);
+
+        let mut files = Vec::new();
+        collect_sources(&[source], true, &mut files).unwrap();
+        assert_eq!(
+            targets(&files),
+            [
+                "src/Main.cs",
+                "src/Mod.csproj",
+                "src/Mod.csproj.user",
+                "src/Util/Helper.cs"
+            ]
+        );
+    }
+
+    #[test]
+    fn output_dir_of_bare_file_name_is_working_directory() {
+        let dir = fixture("output-bare", &[]);
+        let cwd = std::env::current_dir().unwrap();
+        std::env::set_current_dir(&dir.0).unwrap();
+        let result = create_output_dir(Path::new("mod.zip"));
+        std::env::set_current_dir(cwd).unwrap();
+
+        result.unwrap();
+        assert_eq!(fs::read_dir(&dir.0).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn output_dir_existing_parent_is_kept() {
+        let dir = fixture("output-existing", &["dist/Old.zip"]);
+        create_output_dir(&dir.0.join("dist/Mod.zip")).unwrap();
+        assert!(dir.0.join("dist/Old.zip").is_file());
+    }
+
+    #[test]
+    fn output_dir_missing_parents_are_created() {
+        let dir = fixture("output-missing", &[]);
+        create_output_dir(&dir.0.join("dist/nested/Mod.zip")).unwrap();
+        assert!(dir.0.join("dist/nested").is_dir());
+    }
+
+    #[test]
+    fn output_dir_parent_that_is_a_file_fails() {
+        let dir = fixture("output-file", &["dist"]);
+        let error = create_output_dir(&dir.0.join("dist/Mod.zip")).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .starts_with("Output parent is not a directory")
+        );
+
+        let error = create_output_dir(&dir.0).unwrap_err();
+        assert!(error.to_string().starts_with("Output path is a directory"));
+    }
+
+    #[test]
+    fn asset_files_land_at_the_root() {
+        let dir = fixture("assets", &["icon.png", "assets/sprites/unit.png"]);
+        let assets = [
+            dir.0.join("icon.png").to_string_lossy().into_owned(),
+            dir.0.join("assets").to_string_lossy().into_owned(),
+        ];
+
+        let mut files = Vec::new();
+        collect_assets_and_include(&assets.to_vec(), Vec::new(), &mut files).unwrap();
+        assert_eq!(targets(&files), ["icon.png", "sprites/unit.png"]);
+        assert_eq!(files[0].source, dir.0.join("icon.png"));
+    }
+
+    #[test]
+    fn pdbs_of_overlapping_captures_are_added_once() {
+        let dir = fixture(
+            "pdbs",
+            &[
+                "bin/Release/MyMod.dll",
+                "bin/Release/MyMod.pdb",
+                "bin/Release/Excluded.dll",
+                "bin/Release/Excluded.pdb",
+            ],
+        );
+        let assembly = |path: &str| File {
+            source: dir.0.join(path),
+            target: PathBuf::from("MyMod.dll"),
+        };
+
+        // The same assembly captured from the build output and through another path.
+        let files = [
+            assembly("bin/Release/MyMod.dll"),
+            assembly("bin/Release/../Release/MyMod.dll"),
+        ];
+        let pdbs = find_pdbs(&files, 0);
+        assert_eq!(targets(&pdbs), ["MyMod.pdb"]);
+
+        // Symbols already captured under another spelling are not added again.
+        let mut files = files.to_vec();
+        files.push(File {
+            source: dir.0.join("bin/Release/./MyMod.pdb"),
+            target: PathBuf::from("MyMod.pdb"),
+        });
+        assert!(find_pdbs(&files, 0).is_empty());
+
+        // Symbols of an assembly left out of the package are dropped.
+        files.push(File {
+            source: dir.0.join("bin/Release/Excluded.pdb"),
+            target: PathBuf::from("Excluded.pdb"),
+        });
+        drop_orphan_pdbs(&mut files);
+        assert_eq!(targets(&files), ["MyMod.dll", "MyMod.dll", "MyMod.pdb"]);
+    }
+
+    #[test]
+    fn normalized_json_packs_identically() {
+        let dir = fixture("normalize", &[]);
+        let inputs = [
+            "{\"name\": \"MyMod\", \"tags\": [1, 2]}",
+            "\u{feff}{\r\n\t\"tags\":[1,2],\r\n\t\"name\":\"MyMod\"\r\n}",
+        ];
+
+        let mut options = EntryOptions::new(Method::Deflate, None, &[]).unwrap();
+        options.base = options.base.last_modified_time(zip::DateTime::default());
+        let write = WriteOptions {
+            jobs: 1,
+            retries: 0,
+            keep_going: false,
+            force: true,
+            cache: None,
+        };
+
+        let archives = inputs.iter().enumerate().map(|(index, input)| {
+            let source = dir.0.join(format!("{}.json", index));
+            fs::write(&source, input).unwrap();
+            let mut files = [File {
+                source,
+                target: PathBuf::from("mod.json"),
+            }];
+
+            let temp = dir.0.join(format!("normalized-{}", index));
+            normalize_json(&["*.json".to_string()], &mut files, &temp, true).unwrap();
+            let output = dir.0.join(format!("{}.zip", index));
+            zip(&output, &files, &[], &options, &write).unwrap();
+            fs::read(output).unwrap()
+        });
+
+        let archives = archives.collect::<Vec<_>>();
+        assert_eq!(archives[0], archives[1]);
+    }
+
+    #[test]
+    fn satellites_keep_their_locale_folder() {
+        let dir = fixture(
+            "satellites",
+            &[
+                "bin/Release/MyMod.dll",
+                "bin/Release/fr/MyMod.resources.dll",
+                "bin/Release/de/MyMod.resources.dll",
+                "bin/Release/fr/Other.resources.dll",
+            ],
+        );
+        let assembly = dir.0.join("bin/Release/MyMod.dll");
+
+        assert_eq!(artifact_target(&assembly), Path::new("MyMod.dll"));
+        let satellites = find_satellites(&assembly).unwrap();
+        assert_eq!(
+            targets(&satellites),
+            ["de/MyMod.resources.dll", "fr/MyMod.resources.dll"]
+        );
+        assert_eq!(
+            satellites[1].source,
+            dir.0.join("bin/Release/fr/MyMod.resources.dll")
+        );
+    }
+
+    #[test]
+    fn extra_fields_allow_only_zip64() {
+        assert_eq!(unexpected_extra_field(&[]), None);
+
+        let zip64 = [0x01, 0x00, 0x08, 0x00, 0, 0, 0, 0, 1, 0, 0, 0];
+        assert_eq!(unexpected_extra_field(&zip64), None);
+
+        let timestamp = [0x55, 0x54, 0x05, 0x00, 1, 0, 0, 0, 0];
+        assert_eq!(unexpected_extra_field(&timestamp), Some(0x5455));
+        assert_eq!(
+            unexpected_extra_field(&[zip64.as_slice(), &timestamp].concat()),
+            Some(0x5455)
+        );
+
+        assert_eq!(unexpected_extra_field(&[0x01, 0x00]), Some(0xffff));
+        assert_eq!(unexpected_extra_field(&zip64[..6]), Some(0xffff));
+    }
+
+    #[test]
+    fn prune_obj_stays_in_the_project_dir() {
+        let dir = fixture("prune", &["Mod/Mod.csproj", "Mod/obj/a", "Other/obj/b"]);
+        let project = dir.0.join("Mod/Mod.csproj").to_string_lossy().into_owned();
+
+        let dirs = build::project_dirs(&["dotnet".into(), "build".into(), project]);
+        assert_eq!(dirs, [dir.0.join("Mod")]);
+        for dir in &dirs {
+            prune_obj_dirs(dir).unwrap();
+        }
+        assert!(!dir.0.join("Mod/obj").exists());
+        assert!(dir.0.join("Other/obj/b").exists());
+    }
+
+    #[test]
+    fn keep_groups_packages_by_mod_json() {
+        let dir = TempDir::new("test-keep").unwrap();
+        let packages = [
+            ("Mod-1.0", "Mod", "1.0"),
+            ("Mod-1.1", "Mod", "1.1"),
+            ("Mod-1.0-beta", "Mod", "1.0"),
+            ("Mod-2D-1.0", "Mod-2D", "1.0"),
+            ("renamed", "Mod", "0.9"),
+        ];
+        let epoch = SystemTime::UNIX_EPOCH;
+        for (index, (stem, name, version)) in packages.into_iter().enumerate() {
+            let file = fs::File::create(dir.0.join(format!("{}.zip", stem))).unwrap();
+            let mut zip = zip::ZipWriter::new(&file);
+            zip.start_file("mod.json", SimpleFileOptions::default())
+                .unwrap();
+            write!(zip, r#"{{"name": "{}", "version": "{}"}}"#, name, version).unwrap();
+            zip.finish().unwrap();
+            file.set_modified(epoch + Duration::from_secs(index as u64 + 1))
+                .unwrap();
+        }
+
+        assert_eq!(prune_packages(&dir.0, 1, Some("Mod")).unwrap(), 1);
+        assert!(!dir.0.join("Mod-1.0.zip").exists());
+        assert_eq!(prune_packages(&dir.0, 1, None).unwrap(), 0);
+        for stem in ["Mod-1.1", "Mod-1.0-beta", "Mod-2D-1.0", "renamed"] {
+            assert!(dir.0.join(format!("{}.zip", stem)).exists(), "{}", stem);
+        }
+    }
+}
@@ -0,0 +1,306 @@
+use anyhow::{Context, Result, bail};
+
+const CLI_HEADER_DIRECTORY: usize = 14;
+
+const METADATA_SIGNATURE: u32 = 0x424A_5342;
+
+const COMIMAGE_FLAGS_32BITREQUIRED: u32 = 0x02;
+const COMIMAGE_FLAGS_32BITPREFERRED: u32 = 0x0002_0000;
+
+const ASSEMBLY_TABLE: usize = 0x20;
+
+const FIELD: usize = 0x04;
+const METHOD_DEF: usize = 0x06;
+const PARAM: usize = 0x08;
+const TYPE_DEF: usize = 0x02;
+const TYPE_REF: usize = 0x01;
+const EVENT: usize = 0x14;
+const PROPERTY: usize = 0x17;
+const MODULE_REF: usize = 0x1A;
+const TYPE_SPEC: usize = 0x1B;
+const MEMBER_REF: usize = 0x0A;
+
+const TYPE_DEF_OR_REF: (u32, &[usize]) = (2, &[TYPE_DEF, TYPE_REF, TYPE_SPEC]);
+const HAS_CONSTANT: (u32, &[usize]) = (2, &[FIELD, PARAM, PROPERTY]);
+const HAS_CUSTOM_ATTRIBUTE: (u32, &[usize]) = (
+    5,
+    &[
+        0x06, 0x04, 0x01, 0x02, 0x08, 0x09, 0x0A, 0x00, 0x0E, 0x17, 0x14, 0x11, 0x1A, 0x1B, 0x20,
+        0x23, 0x26, 0x27, 0x28, 0x2A, 0x2C, 0x2B,
+    ],
+);
+const HAS_FIELD_MARSHAL: (u32, &[usize]) = (1, &[FIELD, PARAM]);
+const HAS_DECL_SECURITY: (u32, &[usize]) = (2, &[TYPE_DEF, METHOD_DEF, ASSEMBLY_TABLE]);
+const MEMBER_REF_PARENT: (u32, &[usize]) =
+    (3, &[TYPE_DEF, TYPE_REF, MODULE_REF, METHOD_DEF, TYPE_SPEC]);
+const HAS_SEMANTICS: (u32, &[usize]) = (1, &[EVENT, PROPERTY]);
+const METHOD_DEF_OR_REF: (u32, &[usize]) = (1, &[METHOD_DEF, MEMBER_REF]);
+const MEMBER_FORWARDED: (u32, &[usize]) = (1, &[FIELD, METHOD_DEF]);
+const CUSTOM_ATTRIBUTE_TYPE: (u32, &[usize]) = (3, &[METHOD_DEF, MEMBER_REF]);
+const RESOLUTION_SCOPE: (u32, &[usize]) = (2, &[0x00, MODULE_REF, 0x23, TYPE_REF]);
+
+/// Identity of a managed assembly as recorded in its metadata.
+pub struct Assembly {
+    pub name: String,
+    pub version: String,
+}
+
+struct Section {
+    virtual_address: u32,
+    virtual_size: u32,
+    raw_offset: u32,
+}
+
+/// A parsed Portable Executable image.
+pub struct Image<'a> {
+    data: &'a [u8],
+    machine: u16,
+    sections: Vec<Section>,
+    cli_header: Option<u32>,
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16> {
+    data.get(offset..offset + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .context("Unexpected end of image")
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .context("Unexpected end of image")
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Result<u64> {
+    Ok(read_u32(data, offset)? as u64 | (read_u32(data, offset + 4)? as u64) << 32)
+}
+
+fn read_index(data: &[u8], offset: usize, size: usize) -> Result<u32> {
+    if size == 2 {
+        read_u16(data, offset).map(u32::from)
+    } else {
+        read_u32(data, offset)
+    }
+}
+
+fn read_c_str(data: &[u8], offset: usize) -> Result<&str> {
+    let bytes = data.get(offset..).context("Unexpected end of image")?;
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    std::str::from_utf8(&bytes[..end]).context("Invalid string in metadata")
+}
+
+impl<'a> Image<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self> {
+        if data.get(..2) != Some(b"MZ") {
+            bail!("Missing DOS header");
+        }
+
+        let pe = read_u32(data, 0x3C)? as usize;
+        if data.get(pe..pe + 4) != Some(b"PE\0\0") {
+            bail!("Missing PE signature");
+        }
+
+        let coff = pe + 4;
+        let machine = read_u16(data, coff)?;
+        let section_count = read_u16(data, coff + 2)? as usize;
+        let optional_size = read_u16(data, coff + 16)? as usize;
+        let optional = coff + 20;
+
+        let (count_offset, directories) = match read_u16(data, optional)? {
+            0x10B => (92, 96),
+            0x20B => (108, 112),
+            magic => bail!("Unknown optional header magic: {:#x}", magic),
+        };
+
+        let directory_count = read_u32(data, optional + count_offset)? as usize;
+        let cli_header = if directory_count > CLI_HEADER_DIRECTORY {
+            let rva = read_u32(data, optional + directories + CLI_HEADER_DIRECTORY * 8)?;
+            (rva != 0).then_some(rva)
+        } else {
+            None
+        };
+
+        let sections = (0..section_count)
+            .map(|i| {
+                let header = optional + optional_size + i * 40;
+                Ok(Section {
+                    virtual_size: read_u32(data, header + 8)?.max(read_u32(data, header + 16)?),
+                    virtual_address: read_u32(data, header + 12)?,
+                    raw_offset: read_u32(data, header + 20)?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            data,
+            machine,
+            sections,
+            cli_header,
+        })
+    }
+
+    fn offset(&self, rva: u32) -> Result<usize> {
+        let section = self
+            .sections
+            .iter()
+            .find(|s| rva >= s.virtual_address && rva - s.virtual_address < s.virtual_size)
+            .with_context(|| format!("RVA outside of any section: {:#x}", rva))?;
+        (rva - section.virtual_address)
+            .checked_add(section.raw_offset)
+            .map(|offset| offset as usize)
+            .with_context(|| format!("RVA maps outside of the image: {:#x}", rva))
+    }
+
+    /// Returns whether the image carries a CLI header, i.e. is a managed .NET assembly.
+    pub fn is_managed(&self) -> bool {
+        self.cli_header.is_some()
+    }
+
+    /// Describes the processor architecture the image runs on.
+    pub fn architecture(&self) -> Result<&'static str> {
+        let machine = match self.machine {
+            0x014C => "x86",
+            0x8664 => "x64",
+            0xAA64 => "ARM64",
+            0x01C4 => "ARM",
+            _ => "unknown",
+        };
+
+        let Some(cli_header) = self.cli_header else {
+            return Ok(machine);
+        };
+
+        // Managed images targeting any CPU are emitted as 32-bit x86 images.
+        if self.machine != 0x014C {
+            return Ok(machine);
+        }
+
+        let flags = read_u32(self.data, self.offset(cli_header)? + 16)?;
+        Ok(if flags & COMIMAGE_FLAGS_32BITREQUIRED == 0 {
+            "AnyCPU"
+        } else if flags & COMIMAGE_FLAGS_32BITPREFERRED != 0 {
+            "AnyCPU (32-bit preferred)"
+        } else {
+            "x86"
+        })
+    }
+
+    /// Reads the assembly identity, or `None` when the image is not a managed assembly.
+    pub fn assembly(&self) -> Result<Option<Assembly>> {
+        let Some(cli_header) = self.cli_header else {
+            return Ok(None);
+        };
+        let data = self.data;
+
+        let cli = self.offset(cli_header)?;
+        let root = self.offset(read_u32(data, cli + 8)?)?;
+        if read_u32(data, root)? != METADATA_SIGNATURE {
+            bail!("Invalid metadata signature");
+        }
+
+        let version_length = read_u32(data, root + 12)? as usize;
+        let stream_count = read_u16(data, root + 16 + version_length + 2)?;
+
+        let mut tables = None;
+        let mut strings = None;
+        let mut header = root + 16 + version_length + 4;
+        for _ in 0..stream_count {
+            let offset = root + read_u32(data, header)? as usize;
+            let name = read_c_str(data, header + 8)?;
+            match name {
+                "#~" | "#-" => tables = Some(offset),
+                "#Strings" => strings = Some(offset),
+                _ => {}
+            }
+            header += 8 + (name.len() + 4) / 4 * 4;
+        }
+
+        let tables = tables.context("Missing metadata tables stream")?;
+        let strings = strings.context("Missing metadata strings stream")?;
+
+        let heap_sizes = data
+            .get(tables + 6)
+            .copied()
+            .context("Unexpected end of image")?;
+        let string_size = if heap_sizes & 0x01 != 0 { 4 } else { 2 };
+        let guid_size = if heap_sizes & 0x02 != 0 { 4 } else { 2 };
+        let blob_size = if heap_sizes & 0x04 != 0 { 4 } else { 2 };
+
+        let valid = read_u64(data, tables + 8)?;
+        if valid & (1 << ASSEMBLY_TABLE) == 0 {
+            return Ok(None);
+        }
+
+        let mut rows = [0u32; 64];
+        let mut offset = tables + 24;
+        for (table, count) in rows.iter_mut().enumerate() {
+            if valid & (1 << table) != 0 {
+                *count = read_u32(data, offset)?;
+                offset += 4;
+            }
+        }
+        if heap_sizes & 0x40 != 0 {
+            offset += 4;
+        }
+
+        let index = |table: usize| if rows[table] < 1 << 16 { 2 } else { 4 };
+        let coded = |(bits, tables): (u32, &[usize])| {
+            let max = tables.iter().map(|&t| rows[t]).max().unwrap_or(0);
+            if max < 1 << (16 - bits) { 2 } else { 4 }
+        };
+
+        let row_sizes = [
+            2 + string_size + guid_size * 3,
+            coded(RESOLUTION_SCOPE) + string_size * 2,
+            4 + string_size * 2 + coded(TYPE_DEF_OR_REF) + index(FIELD) + index(METHOD_DEF),
+            index(FIELD),
+            2 + string_size + blob_size,
+            index(METHOD_DEF),
+            8 + string_size + blob_size + index(PARAM),
+            index(PARAM),
+            4 + string_size,
+            index(TYPE_DEF) + coded(TYPE_DEF_OR_REF),
+            coded(MEMBER_REF_PARENT) + string_size + blob_size,
+            2 + coded(HAS_CONSTANT) + blob_size,
+            coded(HAS_CUSTOM_ATTRIBUTE) + coded(CUSTOM_ATTRIBUTE_TYPE) + blob_size,
+            coded(HAS_FIELD_MARSHAL) + blob_size,
+            2 + coded(HAS_DECL_SECURITY) + blob_size,
+            6 + index(TYPE_DEF),
+            4 + index(FIELD),
+            blob_size,
+            index(TYPE_DEF) + index(EVENT),
+            index(EVENT),
+            2 + string_size + coded(TYPE_DEF_OR_REF),
+            index(TYPE_DEF) + index(PROPERTY),
+            index(PROPERTY),
+            2 + string_size + blob_size,
+            2 + index(METHOD_DEF) + coded(HAS_SEMANTICS),
+            index(TYPE_DEF) + coded(METHOD_DEF_OR_REF) * 2,
+            string_size,
+            blob_size,
+            2 + coded(MEMBER_FORWARDED) + string_size + index(MODULE_REF),
+            4 + index(FIELD),
+            8,
+            4,
+        ];
+
+        let assembly = offset
+            + row_sizes
+                .iter()
+                .zip(rows)
+                .map(|(size, count)| size * count as usize)
+                .sum::<usize>();
+
+        let version = format!(
+            "{}.{}.{}.{}",
+            read_u16(data, assembly + 4)?,
+            read_u16(data, assembly + 6)?,
+            read_u16(data, assembly + 8)?,
+            read_u16(data, assembly + 10)?
+        );
+        let name = read_index(data, assembly + 16 + blob_size, string_size)?;
+        let name = read_c_str(data, strings + name as usize)?.to_string();
+
+        Ok(Some(Assembly { name, version }))
+    }
+}
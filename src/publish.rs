@@ -0,0 +1,521 @@
+use crate::bump::json_string_range;
+use crate::console::{info, verbose, warning};
+use crate::{git, install, license, manifest};
+use anyhow::{Context, Result, bail};
+use clap::{Args, Subcommand};
+use serde_json::Value;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf, absolute};
+use std::process::{Command, Stdio};
+
+/// Steam app ID of WorldBox.
+const WORLDBOX_APP_ID: u32 = 1206560;
+
+/// Key of 'mod.json' recording the Steam Workshop item.
+const WORKSHOP_ID: &str = "workshopId";
+
+#[derive(Args)]
+pub struct PublishArgs {
+    #[command(subcommand)]
+    target: Target,
+}
+
+#[derive(Subcommand)]
+enum Target {
+    /// Upload the package to the Steam Workshop with steamcmd.
+    Workshop(WorkshopArgs),
+    /// Upload the package and its checksum to the GitHub release of the current tag.
+    Github(GithubArgs),
+}
+
+#[derive(Args)]
+struct WorkshopArgs {
+    /// Path of the packed zip file, the newest one in the default output directory if omitted.
+    #[arg(help = "Path of the packed zip file, the newest in 'bin/Mod' if omitted")]
+    archive: Option<String>,
+
+    /// Steam app ID the item belongs to.
+    #[arg(
+        long,
+        default_value_t = WORLDBOX_APP_ID,
+        help = "Steam app ID the item belongs to"
+    )]
+    app_id: u32,
+
+    /// Whether to use the git 'user.name' and 'user.email' as the author when 'mod.json' has none.
+    /// An author in 'mod.json' always wins, and 'mod.json' itself is left unchanged.
+    #[arg(
+        long,
+        help = "Whether to use the git 'user.name' and 'user.email' as the author when 'mod.json' has none"
+    )]
+    author_from_git: bool,
+
+    /// Change note shown in the item history.
+    #[arg(long, help = "Change note shown in the item history")]
+    change_note: Option<String>,
+
+    /// Path of the 'mod.json' providing the title and description, and receiving the item ID.
+    #[arg(
+        long,
+        default_value = "mod.json",
+        help = "Path of the 'mod.json' providing the title and description"
+    )]
+    mod_json: String,
+
+    /// Preview image of the item, the 'iconPath' of 'mod.json' or 'icon.png' if omitted.
+    #[arg(
+        long,
+        value_name = "IMAGE",
+        help = "Preview image of the item, the icon of the mod if omitted"
+    )]
+    preview: Option<String>,
+
+    /// Path of the steamcmd executable.
+    #[arg(
+        long,
+        default_value = "steamcmd",
+        help = "Path of the steamcmd executable"
+    )]
+    steamcmd: String,
+
+    /// Steam account to log in with, steamcmd prompts for the password and Steam Guard code.
+    #[arg(long, help = "Steam account to log in with")]
+    username: String,
+}
+
+#[derive(Args)]
+struct GithubArgs {
+    /// Path of the packed zip file, the newest one in the default output directory if omitted.
+    #[arg(help = "Path of the packed zip file, the newest in 'bin/Mod' if omitted")]
+    archive: Option<String>,
+
+    /// Base URL of the GitHub REST API, for GitHub Enterprise servers.
+    #[arg(
+        long,
+        default_value = "https://api.github.com",
+        help = "Base URL of the GitHub REST API"
+    )]
+    api_url: String,
+
+    /// Repository as 'owner/name', from 'GITHUB_REPOSITORY' or the 'origin' remote if omitted.
+    #[arg(
+        long,
+        value_name = "OWNER/NAME",
+        help = "Repository as 'owner/name', from 'GITHUB_REPOSITORY' or the 'origin' remote if omitted"
+    )]
+    repo: Option<String>,
+
+    /// Tag of the release, the tag of the checked out commit if omitted.
+    #[arg(
+        long,
+        help = "Tag of the release, the tag of the checked out commit if omitted"
+    )]
+    tag: Option<String>,
+}
+
+/// Quotes a value for a VDF file.
+fn quote(value: &str) -> String {
+    format!(
+        "\"{}\"",
+        value
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n")
+    )
+}
+
+/// Reads the value of a key from a flat VDF file.
+fn vdf_value(content: &str, key: &str) -> Option<String> {
+    content.lines().find_map(|line| {
+        let mut parts = line.split('"').filter(|part| !part.trim().is_empty());
+        match (parts.next(), parts.next()) {
+            (Some(k), Some(value)) if k.eq_ignore_ascii_case(key) => Some(value.to_string()),
+            _ => None,
+        }
+    })
+}
+
+fn string_field<'a>(object: &'a Value, name: &str) -> Option<&'a str> {
+    object
+        .as_object()?
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .and_then(|(_, value)| value.as_str())
+}
+
+/// Sets the workshop item ID in 'mod.json', keeping its formatting.
+fn record_item_id(path: &Path, id: &str) -> Result<()> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read: {}", path.display()))?;
+
+    let updated = match json_string_range(&content, WORKSHOP_ID) {
+        Some((start, end)) => format!("{}{}{}", &content[..start], id, &content[end..]),
+        None => {
+            let close = content
+                .rfind('}')
+                .with_context(|| format!("Expected an object in: {}", path.display()))?;
+            let last = content[..close].trim_end().len();
+            // Follow the indentation of the first field, or stay on one line.
+            let separator = match content.lines().nth(1) {
+                Some(line) => format!(",\n{}", &line[..line.len() - line.trim_start().len()]),
+                None => ", ".to_string(),
+            };
+            format!(
+                "{}{}\"{}\": \"{}\"{}",
+                &content[..last],
+                separator,
+                WORKSHOP_ID,
+                id,
+                &content[last..]
+            )
+        }
+    };
+
+    fs::write(path, updated).with_context(|| format!("Failed to write: {}", path.display()))
+}
+
+/// Detects the SPDX identifier of the license file at the root of an extracted package.
+fn packaged_license(content_dir: &Path) -> Option<&'static str> {
+    fs::read_dir(content_dir)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .filter(|path| license::is_license_file(Path::new(path.file_name().unwrap_or_default())))
+        .find_map(|path| license::detect(&fs::read_to_string(path).ok()?))
+}
+
+fn workshop(args: &WorkshopArgs) -> Result<()> {
+    let archive = match &args.archive {
+        Some(archive) => PathBuf::from(archive),
+        None => install::newest_package()?,
+    };
+    let stem = archive.file_stem().context("Invalid package name")?;
+
+    let mod_json = Path::new(&args.mod_json);
+    let content = fs::read_to_string(mod_json)
+        .with_context(|| format!("Failed to read: {}", mod_json.display()))?;
+    let metadata: Value = serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse: {}", mod_json.display()))?;
+    let title = string_field(&metadata, "name")
+        .with_context(|| format!("No string 'name' in: {}", mod_json.display()))?;
+    let item_id = string_field(&metadata, WORKSHOP_ID).unwrap_or("0");
+
+    let preview = match &args.preview {
+        Some(preview) => PathBuf::from(preview),
+        None => mod_json
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join(string_field(&metadata, "iconPath").unwrap_or("icon.png")),
+    };
+    if !preview.is_file() {
+        bail!("Preview image not found: {}", preview.display());
+    }
+
+    // steamcmd uploads a folder, so the package is extracted as it would be installed.
+    let mut staging = std::env::temp_dir();
+    staging.push(format!("nmlpack-workshop-{}", stem.to_string_lossy()));
+    if staging.exists() {
+        fs::remove_dir_all(&staging)
+            .with_context(|| format!("Failed to remove: {}", staging.display()))?;
+    }
+    let content_dir = staging.join("content");
+    let file = fs::File::open(&archive)
+        .with_context(|| format!("Failed to open: {}", archive.display()))?;
+    zip::ZipArchive::new(file)
+        .with_context(|| format!("Failed to read archive: {}", archive.display()))?
+        .extract(&content_dir)
+        .with_context(|| format!("Failed to extract into: {}", content_dir.display()))?;
+
+    let mut fields = vec![
+        ("appid", args.app_id.to_string()),
+        ("publishedfileid", item_id.to_string()),
+        (
+            "contentfolder",
+            absolute(&content_dir)?.display().to_string(),
+        ),
+        ("previewfile", absolute(&preview)?.display().to_string()),
+        ("title", title.to_string()),
+    ];
+    if let Some(description) = string_field(&metadata, "description") {
+        fields.push(("description", description.to_string()));
+    }
+    let author = match string_field(&metadata, "author") {
+        Some(author) => Some(author.to_string()),
+        None if args.author_from_git => {
+            let author = git::author();
+            if author.is_none() {
+                warning!(
+                    "Skipped '--author-from-git': no 'user.name' or 'user.email' in git config"
+                );
+            }
+            author
+        }
+        None => None,
+    };
+    if let Some(author) = author {
+        fields.push(("author", author));
+    }
+    if let Some(license) = packaged_license(&content_dir) {
+        fields.push(("license", license.to_string()));
+    }
+    if let Some(note) = &args.change_note {
+        fields.push(("changenote", note.clone()));
+    }
+
+    let mut vdf = String::from("\"workshopitem\"\n{\n");
+    for (key, value) in &fields {
+        vdf.push_str(&format!("\t{}\t{}\n", quote(key), quote(value)));
+    }
+    vdf.push_str("}\n");
+
+    let vdf_path = staging.join("workshop_item.vdf");
+    fs::write(&vdf_path, &vdf)
+        .with_context(|| format!("Failed to write: {}", vdf_path.display()))?;
+    verbose!("Wrote: {}", vdf_path.display());
+
+    info!("Uploading {} with: {}", archive.display(), args.steamcmd);
+    let status = Command::new(&args.steamcmd)
+        .arg("+login")
+        .arg(&args.username)
+        .arg("+workshop_build_item")
+        .arg(&vdf_path)
+        .arg("+quit")
+        .status()
+        .with_context(|| format!("Failed to execute: {}", args.steamcmd))?;
+    if !status.success() {
+        bail!("steamcmd failed with {}", status);
+    }
+
+    // steamcmd writes the ID of a newly created item back into the VDF file.
+    let vdf = fs::read_to_string(&vdf_path)
+        .with_context(|| format!("Failed to read: {}", vdf_path.display()))?;
+    let published = vdf_value(&vdf, "publishedfileid")
+        .filter(|id| id != "0")
+        .context("steamcmd did not report a workshop item ID")?;
+
+    if published != item_id {
+        record_item_id(mod_json, &published)?;
+        info!("Recorded workshop item ID in: {}", mod_json.display());
+    }
+    let _ = fs::remove_dir_all(&staging);
+
+    info!(
+        "Published: https://steamcommunity.com/sharedfiles/filedetails/?id={}",
+        published
+    );
+    Ok(())
+}
+
+/// Body of a GitHub API request.
+enum Body<'a> {
+    None,
+    Json(Value),
+    File(&'a Path),
+}
+
+/// Sends a GitHub API request with curl, returning the status code and the parsed response.
+/// The token is passed through the standard input so it does not show up in the process list.
+fn github_request(token: &str, method: &str, url: &str, body: Body) -> Result<(u16, Value)> {
+    let mut command = Command::new("curl");
+    command
+        .args(["--silent", "--show-error", "--location", "--config", "-"])
+        .args(["--request", method, "--write-out", "\n%{http_code}"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped());
+    match &body {
+        Body::None => {}
+        Body::Json(json) => {
+            command
+                .args(["--header", "Content-Type: application/json"])
+                .arg("--data-binary")
+                .arg(json.to_string());
+        }
+        Body::File(path) => {
+            let mut data = std::ffi::OsString::from("@");
+            data.push(path.as_os_str());
+            command
+                .args(["--header", "Content-Type: application/octet-stream"])
+                .arg("--data-binary")
+                .arg(data);
+        }
+    }
+    command.arg(url);
+
+    verbose!("{} {}", method, url);
+    let mut child = command.spawn().context("Failed to execute curl")?;
+    let mut stdin = child.stdin.take().context("Failed to open curl input")?;
+    write!(
+        stdin,
+        "header = \"Authorization: Bearer {}\"\n\
+         header = \"Accept: application/vnd.github+json\"\n\
+         header = \"X-GitHub-Api-Version: 2022-11-28\"\n",
+        token
+    )?;
+    drop(stdin);
+
+    let output = child
+        .wait_with_output()
+        .context("Failed to wait for curl")?;
+    if !output.status.success() {
+        bail!("curl failed with {}: {} {}", output.status, method, url);
+    }
+
+    let output = String::from_utf8_lossy(&output.stdout);
+    let (response, status) = output.rsplit_once('\n').unwrap_or(("", &output));
+    let status = status
+        .trim()
+        .parse()
+        .with_context(|| format!("Invalid response status: {}", status))?;
+    let response = if response.trim().is_empty() {
+        Value::Null
+    } else {
+        serde_json::from_str(response)
+            .with_context(|| format!("Failed to parse response of: {}", url))?
+    };
+
+    Ok((status, response))
+}
+
+/// Fails with the message of an unsuccessful GitHub API response.
+fn check_status(status: u16, response: &Value, action: &str) -> Result<()> {
+    if (200..300).contains(&status) {
+        return Ok(());
+    }
+    let message = response["message"].as_str().unwrap_or("no message");
+    bail!("Failed to {}: HTTP {}: {}", action, status, message);
+}
+
+/// Reads 'owner/name' from a GitHub remote URL in HTTPS or SSH form.
+fn repository_from_url(url: &str) -> Option<String> {
+    let (_, path) = url
+        .split_once("github.com:")
+        .or_else(|| url.split_once("github.com/"))?;
+    let path = path.trim_end_matches('/').trim_end_matches(".git");
+    (path.split('/').count() == 2).then(|| path.to_string())
+}
+
+/// Escapes a value for use in a query string.
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn github(args: &GithubArgs) -> Result<()> {
+    let token = std::env::var("GITHUB_TOKEN")
+        .ok()
+        .filter(|token| !token.is_empty())
+        .context("Set 'GITHUB_TOKEN' to a token allowed to write releases")?;
+
+    let archive = match &args.archive {
+        Some(archive) => PathBuf::from(archive),
+        None => install::newest_package()?,
+    };
+    if !archive.is_file() {
+        bail!("Package not found: {}", archive.display());
+    }
+
+    let repo = match &args.repo {
+        Some(repo) => repo.clone(),
+        None => std::env::var("GITHUB_REPOSITORY")
+            .ok()
+            .or_else(|| git::remote_url().and_then(|url| repository_from_url(&url)))
+            .context("Unable to determine the repository, pass '--repo'")?,
+    };
+    let tag = match &args.tag {
+        Some(tag) => tag.clone(),
+        None => git::exact_tag().context("The checked out commit is not tagged, pass '--tag'")?,
+    };
+
+    let api = format!(
+        "{}/repos/{}/releases",
+        args.api_url.trim_end_matches('/'),
+        repo
+    );
+    let (status, mut release) = github_request(
+        &token,
+        "GET",
+        &format!("{}/tags/{}", api, percent_encode(&tag)),
+        Body::None,
+    )?;
+    if status == 404 {
+        let (status, created) = github_request(
+            &token,
+            "POST",
+            &api,
+            Body::Json(serde_json::json!({ "tag_name": tag, "name": tag })),
+        )?;
+        check_status(status, &created, "create release")?;
+        info!("Created release: {}", tag);
+        release = created;
+    } else {
+        check_status(status, &release, "read release")?;
+        info!("Updating release: {}", tag);
+    }
+
+    let upload_url = release["upload_url"]
+        .as_str()
+        .context("Release has no upload URL")?;
+    let upload_url = upload_url
+        .split('{')
+        .next()
+        .unwrap_or(upload_url)
+        .to_string();
+    let existing = release["assets"].as_array().cloned().unwrap_or_default();
+
+    manifest::write_checksum(&archive)?;
+    let checksum = crate::signing::with_suffix(&archive, ".sha256");
+
+    for path in [archive.as_path(), checksum.as_path()] {
+        let name = path
+            .file_name()
+            .context("Invalid asset name")?
+            .to_string_lossy()
+            .to_string();
+
+        // Assets cannot be overwritten, a previous upload with the same name is deleted first.
+        if let Some(id) = existing
+            .iter()
+            .find(|asset| asset["name"] == name.as_str())
+            .and_then(|asset| asset["id"].as_u64())
+        {
+            let (status, response) = github_request(
+                &token,
+                "DELETE",
+                &format!("{}/assets/{}", api, id),
+                Body::None,
+            )?;
+            check_status(status, &response, "delete previous asset")?;
+            verbose!("Deleted previous asset: {}", name);
+        }
+
+        let (status, response) = github_request(
+            &token,
+            "POST",
+            &format!("{}?name={}", upload_url, percent_encode(&name)),
+            Body::File(path),
+        )?;
+        check_status(status, &response, "upload asset")?;
+        info!("Uploaded: {}", name);
+    }
+
+    if let Some(url) = release["html_url"].as_str() {
+        info!("Published: {}", url);
+    }
+    Ok(())
+}
+
+pub fn run(args: &PublishArgs) -> Result<()> {
+    match &args.target {
+        Target::Workshop(args) => workshop(args),
+        Target::Github(args) => github(args),
+    }
+}
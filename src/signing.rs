@@ -0,0 +1,413 @@
+use crate::console::info;
+use crate::manifest;
+use anyhow::{Context, Result, anyhow, bail};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use blake2::digest::consts::U32;
+use blake2::{Blake2b, Blake2b512};
+use clap::Args;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Args)]
+pub struct VerifyArgs {
+    /// Path of the packed zip file.
+    #[arg(help = "Path of the packed zip file")]
+    archive: String,
+
+    /// Path of the ed25519 public key, hex-encoded or in minisign format.
+    #[arg(
+        long,
+        value_name = "KEY",
+        help = "Path of the ed25519 public key, hex-encoded or in minisign format"
+    )]
+    public_key: String,
+
+    /// Whether to check the signed manifest '<archive>.manifest' instead of a detached signature.
+    /// The signature of the manifest is checked first, then the checksum of every entry.
+    #[arg(
+        long,
+        conflicts_with = "sig",
+        help = "Whether to check the signed manifest '<archive>.manifest' instead of a detached signature"
+    )]
+    manifest: bool,
+
+    /// Path of the detached signature, '<archive>.sig' if omitted.
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Path of the detached signature, '<archive>.sig' if omitted"
+    )]
+    sig: Option<String>,
+}
+
+/// Appends a suffix to the full file name, e.g. 'mod.zip' to 'mod.zip.sig'.
+pub fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn from_hex<const N: usize>(text: &str) -> Option<[u8; N]> {
+    let text = text.trim();
+    if text.len() != N * 2 || !text.is_ascii() {
+        return None;
+    }
+
+    let mut bytes = [0u8; N];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&text[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(bytes)
+}
+
+/// Environment variable holding the passphrase of an encrypted key, read instead of prompting.
+const PASSPHRASE_VAR: &str = "NMLPACKER_KEY_PASSPHRASE";
+
+/// Length of a minisign secret key: algorithms, KDF salt and limits, then the encrypted key number, key and checksum.
+const MINISIGN_SECRET_KEY_LEN: usize = 2 + 2 + 2 + 32 + 8 + 8 + 104;
+
+/// Length of a minisign public key: algorithm, key number and key.
+const MINISIGN_PUBLIC_KEY_LEN: usize = 2 + 8 + 32;
+
+/// Decodes the base64 line of a minisign key file, after its 'untrusted comment:' line.
+fn minisign_payload(content: &str) -> Option<Vec<u8>> {
+    let line = content
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with("untrusted comment:"))?;
+    BASE64.decode(line).ok()
+}
+
+/// Asks for the passphrase of an encrypted key, unless it is set in the environment.
+fn passphrase(path: &Path) -> Result<String> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_VAR) {
+        return Ok(passphrase);
+    }
+    if !std::io::stdin().is_terminal() {
+        bail!(
+            "Key is protected by a passphrase, set '{}' to use it non-interactively: {}",
+            PASSPHRASE_VAR,
+            path.display()
+        );
+    }
+    rpassword::prompt_password(format!("Passphrase for {}: ", path.display()))
+        .context("Failed to read passphrase")
+}
+
+/// scrypt parameters for libsodium's 'opslimit' and 'memlimit', as picked by minisign.
+fn scrypt_params(opslimit: u64, memlimit: u64) -> Result<scrypt::Params> {
+    let opslimit = opslimit.max(32768);
+    let r = 8u64;
+    let max_n = if opslimit < memlimit / 32 {
+        opslimit / (r * 4)
+    } else {
+        memlimit / (r * 128)
+    };
+    let log_n = (1..63).find(|&n| 1u64 << n > max_n / 2).unwrap_or(63);
+    let p = if opslimit < memlimit / 32 {
+        1
+    } else {
+        ((opslimit / 4) >> log_n).min(0x3fff_ffff) / r
+    };
+    scrypt::Params::new(log_n as u8, r as u32, p.max(1) as u32, 104)
+        .map_err(|_| anyhow!("Invalid key derivation parameters"))
+}
+
+/// Decrypts a minisign secret key, prompting for its passphrase when it has one.
+/// Returns its key number and seed.
+fn minisign_secret_key(path: &Path, key: &[u8]) -> Result<([u8; 8], [u8; 32])> {
+    let (algorithm, rest) = key.split_at(2);
+    let (kdf, rest) = rest.split_at(2);
+    let (_checksum_algorithm, rest) = rest.split_at(2);
+    let (salt, rest) = rest.split_at(32);
+    let (opslimit, rest) = rest.split_at(8);
+    let (memlimit, encrypted) = rest.split_at(8);
+    if algorithm != b"Ed" {
+        bail!("Unsupported minisign key algorithm: {}", path.display());
+    }
+
+    let mut keynum = encrypted.to_vec();
+    match kdf {
+        b"Sc" => {
+            let params = scrypt_params(
+                u64::from_le_bytes(opslimit.try_into()?),
+                u64::from_le_bytes(memlimit.try_into()?),
+            )?;
+            let mut stream = [0u8; 104];
+            scrypt::scrypt(passphrase(path)?.as_bytes(), salt, &params, &mut stream)
+                .map_err(|_| anyhow!("Failed to derive key from passphrase"))?;
+            keynum.iter_mut().zip(stream).for_each(|(b, s)| *b ^= s);
+        }
+        [0, 0] => {}
+        _ => bail!("Unsupported minisign key derivation: {}", path.display()),
+    }
+
+    let (id_and_key, checksum) = keynum.split_at(72);
+    let mut hasher = Blake2b::<U32>::new();
+    hasher.update(algorithm);
+    hasher.update(id_and_key);
+    if hasher.finalize().as_slice() != checksum {
+        bail!("Wrong passphrase or corrupt key: {}", path.display());
+    }
+    Ok((id_and_key[..8].try_into()?, id_and_key[8..40].try_into()?))
+}
+
+/// An ed25519 secret key, with the key number of the minisign key file it was read from.
+pub struct SecretKey {
+    key: SigningKey,
+    keynum: Option<[u8; 8]>,
+}
+
+impl SecretKey {
+    /// Hex-encoded public key.
+    pub fn public_hex(&self) -> String {
+        to_hex(&self.key.verifying_key().to_bytes())
+    }
+}
+
+/// An ed25519 public key, with the key number of the minisign key file it was read from.
+pub struct PublicKey {
+    key: VerifyingKey,
+    keynum: Option<[u8; 8]>,
+}
+
+/// Loads an ed25519 secret key stored as 64 hex digits or as a minisign secret key.
+pub fn signing_key(path: &Path) -> Result<SecretKey> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read: {}", path.display()))?;
+    let (keynum, seed) = match from_hex(&content) {
+        Some(seed) => (None, seed),
+        None => match minisign_payload(&content) {
+            Some(key) if key.len() == MINISIGN_SECRET_KEY_LEN => {
+                let (keynum, seed) = minisign_secret_key(path, &key)?;
+                (Some(keynum), seed)
+            }
+            _ => bail!(
+                "Expected 32 hex-encoded bytes or a minisign secret key: {}",
+                path.display()
+            ),
+        },
+    };
+    Ok(SecretKey {
+        key: SigningKey::from_bytes(&seed),
+        keynum,
+    })
+}
+
+/// Loads an ed25519 public key stored as 64 hex digits or as a minisign public key.
+pub fn verifying_key(path: &Path) -> Result<PublicKey> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read: {}", path.display()))?;
+    let (keynum, bytes) = match from_hex(&content) {
+        Some(bytes) => (None, bytes),
+        None => match minisign_payload(&content) {
+            Some(key) if key.len() == MINISIGN_PUBLIC_KEY_LEN && key.starts_with(b"Ed") => {
+                (Some(key[2..10].try_into()?), key[10..].try_into()?)
+            }
+            _ => bail!(
+                "Expected 32 hex-encoded bytes or a minisign public key: {}",
+                path.display()
+            ),
+        },
+    };
+    let key = VerifyingKey::from_bytes(&bytes)
+        .with_context(|| format!("Invalid public key: {}", path.display()))?;
+    Ok(PublicKey { key, keynum })
+}
+
+fn file_digest(path: &Path) -> Result<Vec<u8>> {
+    let mut file =
+        fs::File::open(path).with_context(|| format!("Failed to open: {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)
+        .with_context(|| format!("Failed to read: {}", path.display()))?;
+    Ok(hasher.finalize().to_vec())
+}
+
+/// BLAKE2b-512 of a file, which prehashed minisign signatures cover.
+fn blake2b_digest(path: &Path) -> Result<Vec<u8>> {
+    let mut file =
+        fs::File::open(path).with_context(|| format!("Failed to open: {}", path.display()))?;
+    let mut hasher = Blake2b512::new();
+    std::io::copy(&mut file, &mut hasher)
+        .with_context(|| format!("Failed to read: {}", path.display()))?;
+    Ok(hasher.finalize().to_vec())
+}
+
+/// Signs a file into a signature file.
+/// Hex keys write the hex-encoded signature of 'message'; minisign keys write a signature
+/// of the file in minisign's format, which 'minisign -V' verifies.
+pub fn write_signature(
+    key: &SecretKey,
+    path: &Path,
+    message: &[u8],
+    signature_path: &Path,
+) -> Result<()> {
+    let content = match key.keynum {
+        None => format!("{}\n", to_hex(&key.key.sign(message).to_bytes())),
+        Some(keynum) => {
+            let signature = key.key.sign(&blake2b_digest(path)?).to_bytes();
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            let trusted = format!("timestamp:{}\tfile:{}\tprehashed", timestamp, file_name);
+            let global = key
+                .key
+                .sign(&[&signature[..], trusted.as_bytes()].concat())
+                .to_bytes();
+            format!(
+                "untrusted comment: signature from nmlpack secret key\n{}\ntrusted comment: {}\n{}\n",
+                BASE64.encode([&b"ED"[..], &keynum, &signature].concat()),
+                trusted,
+                BASE64.encode(global)
+            )
+        }
+    };
+    fs::write(signature_path, content)
+        .with_context(|| format!("Failed to write: {}", signature_path.display()))
+}
+
+/// Checks a signature file written by 'write_signature', in either format.
+pub fn check_signature(
+    key: &PublicKey,
+    path: &Path,
+    message: &[u8],
+    signature_path: &Path,
+) -> Result<()> {
+    let content = fs::read_to_string(signature_path)
+        .with_context(|| format!("Failed to read: {}", signature_path.display()))?;
+    let invalid = || anyhow!("Invalid signature: {}", signature_path.display());
+    let mismatch = || format!("Signature does not match: {}", path.display());
+
+    if let Some(bytes) = from_hex::<64>(&content) {
+        return key
+            .key
+            .verify(message, &Signature::from_bytes(&bytes))
+            .with_context(mismatch);
+    }
+
+    let mut lines = content
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !line.starts_with("untrusted comment:"));
+    let signature = lines.next().and_then(|line| BASE64.decode(line).ok());
+    let trusted = lines
+        .next()
+        .and_then(|line| line.strip_prefix("trusted comment: "));
+    let global = lines.next().and_then(|line| BASE64.decode(line).ok());
+    let (Some(signature), Some(trusted), Some(global)) = (signature, trusted, global) else {
+        return Err(invalid());
+    };
+    if signature.len() != 74 {
+        return Err(invalid());
+    }
+
+    let (algorithm, rest) = signature.split_at(2);
+    let (keynum, signature) = rest.split_at(8);
+    if key.keynum.is_some_and(|expected| expected != keynum) {
+        bail!(
+            "Signature was made by a different key: {}",
+            signature_path.display()
+        );
+    }
+    let data = match algorithm {
+        b"ED" => blake2b_digest(path)?,
+        b"Ed" => fs::read(path).with_context(|| format!("Failed to read: {}", path.display()))?,
+        _ => return Err(invalid()),
+    };
+
+    let signature = Signature::from_slice(signature).map_err(|_| invalid())?;
+    key.key.verify(&data, &signature).with_context(mismatch)?;
+    let global = Signature::from_slice(&global).map_err(|_| invalid())?;
+    key.key
+        .verify(
+            &[&signature.to_bytes()[..], trusted.as_bytes()].concat(),
+            &global,
+        )
+        .with_context(|| {
+            format!(
+                "Trusted comment does not match: {}",
+                signature_path.display()
+            )
+        })
+}
+
+/// Signs the SHA-256 digest of a file into '<file>.sig', returning the signature path and hex public key.
+pub fn sign(path: &Path, key: &Path) -> Result<(PathBuf, String)> {
+    let key = signing_key(key)?;
+    let signature_path = with_suffix(path, ".sig");
+    write_signature(&key, path, &file_digest(path)?, &signature_path)?;
+    Ok((signature_path, key.public_hex()))
+}
+
+/// Checks a detached signature made by 'sign'.
+pub fn verify(path: &Path, public_key: &Path, signature: &Path) -> Result<()> {
+    let key = verifying_key(public_key)?;
+    check_signature(&key, path, &file_digest(path)?, signature)
+}
+
+pub fn run(args: &VerifyArgs) -> Result<()> {
+    let archive = Path::new(&args.archive);
+    if args.manifest {
+        let count = manifest::verify(archive, Path::new(&args.public_key))?;
+        info!("Verified {} entries: {}", count, archive.display());
+        return Ok(());
+    }
+
+    let signature = match &args.sig {
+        Some(sig) => PathBuf::from(sig),
+        None => with_suffix(archive, ".sig"),
+    };
+
+    verify(archive, Path::new(&args.public_key), &signature)?;
+    info!("Signature is valid: {}", archive.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pack::TempDir;
+
+    #[test]
+    fn minisign_signatures_verify() {
+        let dir = TempDir::new("test-minisign").unwrap();
+        let path = dir.0.join("MyMod-1.0.0.zip");
+        let signature_path = with_suffix(&path, ".sig");
+        fs::write(&path, b"package").unwrap();
+
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let public = PublicKey {
+            key: key.verifying_key(),
+            keynum: Some([1; 8]),
+        };
+        let secret = SecretKey {
+            key,
+            keynum: Some([1; 8]),
+        };
+        write_signature(&secret, &path, b"", &signature_path).unwrap();
+        let content = fs::read_to_string(&signature_path).unwrap();
+        assert!(content.starts_with("untrusted comment: "));
+        check_signature(&public, &path, b"", &signature_path).unwrap();
+
+        let other = PublicKey {
+            key: public.key,
+            keynum: Some([2; 8]),
+        };
+        assert!(check_signature(&other, &path, b"", &signature_path).is_err());
+
+        fs::write(&signature_path, content.replace("file:", "file:x")).unwrap();
+        assert!(check_signature(&public, &path, b"", &signature_path).is_err());
+    }
+}
@@ -0,0 +1,162 @@
+use crate::console::{self, error, info, verbose};
+use crate::install;
+use crate::pack::{self, PackArgs};
+use anyhow::{Context, Result, bail};
+use clap::Args;
+use notify::{RecursiveMode, Watcher};
+use std::path::{Path, PathBuf, absolute};
+use std::sync::mpsc;
+use std::time::Duration;
+
+#[derive(Args)]
+pub struct WatchArgs {
+    /// Milliseconds without changes to wait for before repacking.
+    #[arg(
+        long,
+        default_value_t = 500,
+        value_name = "MS",
+        help = "Milliseconds without changes to wait for before repacking"
+    )]
+    debounce: u64,
+
+    /// Whether to install the package into WorldBox after every pack.
+    #[arg(
+        long,
+        help = "Whether to install the package into WorldBox after every pack"
+    )]
+    install: bool,
+
+    #[command(flatten)]
+    pack: PackArgs,
+}
+
+fn pack_once(args: &WatchArgs) {
+    // Every repack replaces the previous package.
+    let result = pack::run(&args.pack.forced()).and_then(|archives| {
+        if !args.install {
+            return Ok(());
+        }
+        match archives.as_slice() {
+            [] => Ok(()),
+            [archive] => install::install(Some(archive), None, false, false),
+            _ => bail!("A package split into volumes cannot be installed"),
+        }
+    });
+
+    if let Err(e) = result {
+        error!("Error: {:?}", e);
+    }
+}
+
+/// Whether every changed path lies inside one of the ignored directories.
+fn is_ignored(paths: &[PathBuf], ignored: &[PathBuf]) -> bool {
+    !paths.is_empty()
+        && paths
+            .iter()
+            .all(|path| ignored.iter().any(|dir| path.starts_with(dir)))
+}
+
+/// Packs once, then repacks whenever an input changes.
+pub fn run(args: &WatchArgs) -> Result<()> {
+    console::set_level(args.pack.level());
+
+    if args.pack.workspace_selection().is_some() {
+        bail!("'watch' packs a single mod, run it in the directory of the mod");
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender).context("Failed to start watcher")?;
+
+    let mut watched = 0;
+    for path in args.pack.inputs() {
+        let mode = if path.is_dir() {
+            RecursiveMode::Recursive
+        } else if path.is_file() {
+            RecursiveMode::NonRecursive
+        } else {
+            continue;
+        };
+
+        watcher
+            .watch(&path, mode)
+            .with_context(|| format!("Failed to watch: {}", path.display()))?;
+        verbose!("Watching: {}", path.display());
+        watched += 1;
+    }
+    if watched == 0 {
+        bail!("None of the input paths exist");
+    }
+
+    // Writing the package must not trigger another pack when it lies inside a watched directory.
+    let ignored = [
+        args.pack.output_dir(),
+        PathBuf::from("obj"),
+        PathBuf::from("bin"),
+    ]
+    .iter()
+    .map(|dir| absolute(dir).unwrap_or_else(|_| dir.clone()))
+    .collect::<Vec<_>>();
+
+    pack_once(args);
+    info!(
+        "Watching {} paths for changes, press Ctrl+C to stop",
+        watched
+    );
+
+    let debounce = Duration::from_millis(args.debounce);
+    loop {
+        let event = receiver.recv().context("Watcher stopped")?;
+        let mut changed = Vec::new();
+        let mut pending = Some(event);
+
+        // Collect further events until the inputs have been quiet for the debounce period.
+        while let Some(event) = pending {
+            let event = event.context("Failed to watch for changes")?;
+            if !event.kind.is_access() && !is_ignored(&event.paths, &ignored) {
+                changed.extend(event.paths);
+            }
+            pending = receiver.recv_timeout(debounce).ok();
+        }
+
+        if changed.is_empty() {
+            continue;
+        }
+
+        changed.sort();
+        changed.dedup();
+        for path in &changed {
+            verbose!("Changed: {}", display(path));
+        }
+
+        info!();
+        info!("Detected {} changed paths, repacking", changed.len());
+        pack_once(args);
+    }
+}
+
+fn display(path: &Path) -> String {
+    std::env::current_dir()
+        .ok()
+        .and_then(|dir| path.strip_prefix(dir).ok())
+        .unwrap_or(path)
+        .display()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_on_the_output_are_ignored() {
+        let ignored = [PathBuf::from("/mod/bin/Mod"), PathBuf::from("/mod/obj")];
+        let output = PathBuf::from("/mod/bin/Mod/MyMod-1.0.0.zip");
+        let pending = PathBuf::from("/mod/bin/Mod/MyMod-1.0.0.zip.tmp");
+        let asset = PathBuf::from("/mod/assets/icon.png");
+
+        assert!(is_ignored(&[output.clone(), pending], &ignored));
+        assert!(!is_ignored(&[output, asset.clone()], &ignored));
+        assert!(!is_ignored(&[asset], &ignored));
+        assert!(!is_ignored(&[], &ignored));
+    }
+}
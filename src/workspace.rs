@@ -0,0 +1,180 @@
+use crate::config;
+use crate::console::{self, error};
+use anyhow::{Context, Result, bail};
+use std::fmt::Arguments;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the workspace file, looked up in the working directory.
+const FILE_NAME: &str = "nmlpacker.workspace.toml";
+
+/// Mods selected on the command line: workspace members, or every mod below a directory.
+pub enum Selection<'a> {
+    All,
+    Member(&'a str),
+    Recursive(&'a str),
+}
+
+/// Options of the workspace file, shared by every member.
+pub struct Shared {
+    /// Path of the workspace file, for error messages.
+    pub path: PathBuf,
+    pub options: toml::Table,
+}
+
+/// Reads the member directories listed by the workspace file, leaving the shared options.
+fn members(path: &Path, options: &mut toml::Table) -> Result<Vec<String>> {
+    let invalid = |expected: &str, found: &toml::Value| {
+        anyhow::anyhow!(
+            "Invalid value for 'members' in {}: expected {}, found {}",
+            path.display(),
+            expected,
+            found.type_str()
+        )
+    };
+
+    match options.remove("members") {
+        Some(toml::Value::Array(members)) => members
+            .into_iter()
+            .map(|member| match member {
+                toml::Value::String(member) => Ok(member),
+                other => Err(invalid("an array of paths", &other)),
+            })
+            .collect(),
+        Some(other) => Err(invalid("an array of paths", &other)),
+        None => bail!("Missing 'members' in: {}", path.display()),
+    }
+}
+
+/// Directories never searched for mods, holding build output rather than projects.
+const SKIPPED_DIRS: &[&str] = &["bin", "obj"];
+
+/// Collects the directories below a directory holding a 'mod.json', without descending into them.
+fn find_mods(dir: &Path, found: &mut Vec<PathBuf>) -> Result<()> {
+    if dir.join("mod.json").is_file() {
+        found.push(dir.to_path_buf());
+        return Ok(());
+    }
+
+    let entries = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read directory: {}", dir.display()))?;
+    let mut dirs = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        let skipped = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with('.') || SKIPPED_DIRS.contains(&name));
+        if path.is_dir() && !skipped {
+            dirs.push(path);
+        }
+    }
+    dirs.sort();
+    for dir in dirs {
+        find_mods(&dir, found)?;
+    }
+    Ok(())
+}
+
+/// Reads the workspace file in the working directory, returning its members and shared options.
+fn read_workspace(root: &Path) -> Result<(Vec<String>, Shared)> {
+    let path = Path::new(FILE_NAME);
+    if !path.is_file() {
+        bail!("'--all' and members need a workspace file: {}", FILE_NAME);
+    }
+
+    let mut options = config::read(path)?;
+    let members = members(path, &mut options)?;
+    Ok((
+        members,
+        Shared {
+            path: root.join(path),
+            options,
+        },
+    ))
+}
+
+/// Runs a command in the directory of every selected mod, passing the options shared by a workspace.
+/// Workspace members are selected by their path in the workspace file or by their directory name.
+/// With several mods, all are packed even if some fail, and a table of the results follows.
+/// With 'json', progress and the table go to stderr, leaving stdout to the JSON summaries.
+pub fn run(
+    selection: Selection,
+    json: bool,
+    mut run: impl FnMut(Option<&Shared>) -> Result<()>,
+) -> Result<()> {
+    let print = |line: Arguments| {
+        if json {
+            console::print_error(line)
+        } else {
+            console::print_info(line)
+        }
+    };
+
+    let root = std::env::current_dir().context("Failed to read the working directory")?;
+
+    let (mods, shared) = match selection {
+        Selection::All => {
+            let (members, shared) = read_workspace(&root)?;
+            (members, Some(shared))
+        }
+        Selection::Member(name) => {
+            let (members, shared) = read_workspace(&root)?;
+            let member = members
+                .into_iter()
+                .find(|member| {
+                    member == name || Path::new(member).file_name().is_some_and(|n| n == name)
+                })
+                .with_context(|| format!("Unknown workspace member: {}", name))?;
+            (vec![member], Some(shared))
+        }
+        Selection::Recursive(dir) => {
+            let mut found = Vec::new();
+            find_mods(Path::new(dir), &mut found)?;
+            if found.is_empty() {
+                bail!("No directory with a 'mod.json' found below: {}", dir);
+            }
+            let mods = found
+                .iter()
+                .map(|path| path.to_string_lossy().into_owned())
+                .collect();
+            (mods, None)
+        }
+    };
+
+    let mut results = Vec::new();
+    for name in &mods {
+        print(format_args!("Packing mod in: {}", name));
+        let dir = root.join(name);
+        std::env::set_current_dir(&dir)
+            .with_context(|| format!("Failed to enter directory: {}", dir.display()))?;
+
+        let result = run(shared.as_ref());
+        std::env::set_current_dir(&root)
+            .with_context(|| format!("Failed to enter directory: {}", root.display()))?;
+
+        if mods.len() == 1 {
+            return result.with_context(|| format!("Failed to pack mod in: {}", name));
+        }
+        if let Err(e) = &result {
+            error!("Error: {:#}", e);
+        }
+        results.push((name, result));
+    }
+
+    let width = mods.iter().map(|name| name.len()).max().unwrap_or_default();
+    print(format_args!(""));
+    print(format_args!("{:<width$}  Result", "Mod"));
+    for (name, result) in &results {
+        match result {
+            Ok(()) => print(format_args!("{:<width$}  packed", name)),
+            Err(e) => print(format_args!("{:<width$}  failed: {}", name, e)),
+        }
+    }
+
+    let failed = results.iter().filter(|(_, result)| result.is_err()).count();
+    if failed > 0 {
+        bail!("{} of {} mods failed to pack", failed, results.len());
+    }
+    Ok(())
+}